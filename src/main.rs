@@ -1,145 +1,4277 @@
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet};
 use std::env;
+use std::ffi::OsStr;
+use std::fmt;
 use std::fs::{self, File};
-use std::io::Write;
-use std::path::PathBuf;
+use std::io::{self, IsTerminal, Write};
+use std::path::{Component, Path, PathBuf};
+#[cfg(unix)]
+use std::os::unix::fs::PermissionsExt;
+use std::process;
 use serde::{Deserialize, Serialize};
-use clap::Parser;
+use clap::{ArgGroup, CommandFactory, Parser, ValueEnum};
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde_json::json;
 
-#[derive(Deserialize, Serialize, Debug)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
 struct Bookmark {
     alias: String,
     path: PathBuf,
+    // More places the same directory may be found, e.g. where a project is checked out on another machine.
+    // `jump` goes to the first of `path` and these that exists. Added with `add --append`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    alternate_paths: Vec<PathBuf>,
+    #[serde(default)]
+    visits: u64,
+    #[serde(default)]
+    last_visited: Option<DateTime<Utc>>,
+    #[serde(default)]
+    created_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pinned: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    on_jump: Option<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    expand_on_jump: bool,
+    // Whether the bookmark is a directory, the default, or a file (see BookmarkKind).
+    #[serde(default, skip_serializing_if = "BookmarkKind::is_dir")]
+    kind: BookmarkKind,
+    // Set on bookmarks merged in from the system config (see system_config_path), which are never saved.
+    #[serde(skip)]
+    system: bool,
+    // Fields this version doesn't know, e.g. from a newer pomelo sharing the config, kept so saving doesn't drop them.
+    #[serde(flatten)]
+    unknown: UnknownFields,
 }
 
-#[derive(Deserialize, Serialize, Debug)]
+// The keys of a table this version doesn't know, with their values (see Bookmark::unknown). A null, which JSON and YAML
+// have but TOML doesn't, is kept as None, and left out when the config is saved as TOML.
+type UnknownFields = BTreeMap<String, Option<toml::Value>>;
+
+impl Bookmark {
+    // Creates a bookmark that was never visited, stamped with the current time.
+    fn new(alias: String, path: PathBuf) -> Self {
+        Bookmark {
+            alias,
+            path,
+            alternate_paths: Vec::new(),
+            visits: 0,
+            last_visited: None,
+            created_at: Some(Utc::now()),
+            tags: Vec::new(),
+            description: None,
+            pinned: false,
+            on_jump: None,
+            expand_on_jump: false,
+            kind: BookmarkKind::Dir,
+            system: false,
+            unknown: BTreeMap::new(),
+        }
+    }
+
+    // Refuses to change a bookmark from the system config, since pomelo never writes that file. A bookmark of the
+    // user's own with the same alias overrides it instead.
+    fn check_changeable(&self) -> Result<(), String> {
+        if self.system {
+            return Err(format!(
+                "'{}' comes from the system config, so it can't be changed. Use `pomelo add --force -a {}` to override it with your own.",
+                self.alias,
+                self.alias
+            ));
+        }
+        Ok(())
+    }
+
+    // The directory the bookmark points at right now: the first of its path and alternate paths that exists, or
+    // its path when none of them does. Paths added with --expand-on-jump are templates, whose environment variables
+    // are expanded on every use, failing if one of them isn't set.
+    fn target(&self) -> Result<PathBuf, String> {
+        let existing = self.alternate_paths
+            .iter()
+            .filter_map(|path| self.expand(path).ok())
+            .find(|path| path.exists());
+        match (self.primary_target(), existing) {
+            (Ok(path), _) if path.exists() => Ok(path),
+            (_, Some(path)) => Ok(path),
+            (primary, None) => primary,
+        }
+    }
+
+    // The bookmark's own path, with a template expanded, whether or not it exists.
+    fn primary_target(&self) -> Result<PathBuf, String> {
+        self.expand(&self.path)
+    }
+
+    fn expand(&self, path: &Path) -> Result<PathBuf, String> {
+        if self.expand_on_jump {
+            expand_path(path)
+        } else {
+            Ok(path.to_path_buf())
+        }
+    }
+
+    // Checks that what's at the bookmark's path is still the kind of thing that was bookmarked, e.g. that a file
+    // wasn't replaced by a directory of the same name.
+    fn check_kind(&self, path: &Path) -> Result<(), String> {
+        match self.kind {
+            BookmarkKind::Dir if !path.is_dir() => Err(format!("'{}' is not a directory anymore", path.display())),
+            BookmarkKind::File if !path.is_file() => Err(format!("'{}' is not a file anymore", path.display())),
+            _ => Ok(()),
+        }
+    }
+
+    // Whether the bookmarked directory is gone. A template using variables that aren't set right now doesn't count,
+    // so `prune` keeps it around for when they are.
+    fn is_missing(&self) -> bool {
+        self.target().is_ok_and(|path| !path.exists())
+    }
+}
+
+// What a bookmark points at. Directories are what pomelo is for, but a file, like a config file edited often, can
+// be bookmarked with `add --file` too. Jumping to a file goes to the directory it's in.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum BookmarkKind {
+    #[default]
+    Dir,
+    File,
+}
+
+impl BookmarkKind {
+    fn is_dir(&self) -> bool {
+        *self == BookmarkKind::Dir
+    }
+}
+
+// The version of the config format this build reads and writes. Bump it, and teach migrate_config about the old
+// version, whenever the format changes in a way older configs need converting for.
+const CONFIG_VERSION: u32 = 1;
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
 struct Config {
+    // Configs written before versioning was introduced have no version field and load as version 0.
+    #[serde(default)]
+    version: u32,
+    #[serde(default, skip_serializing_if = "Settings::is_default")]
+    settings: Settings,
+    // TOML can't have an empty array after the [settings] table, so a config without bookmarks leaves the key out.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     bookmarks: Vec<Bookmark>,
+    // Like Bookmark::unknown, top-level keys this version doesn't know.
+    #[serde(flatten)]
+    unknown: UnknownFields,
+}
+
+// Preferences kept in the config file's [settings] table. Every setting has a default, so the table and each
+// key in it are optional. Settings are only written when they differ from their default, so that a later change
+// of a default reaches everyone who never changed the setting.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+struct Settings {
+    // Removes a bookmark when `jump` finds that its directory no longer exists, as with `jump --prune-missing`.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    prune_on_missing: bool,
+    // Writes the bookmarks sorted by alias, so the file diffs and merges cleanly when it's kept in version control.
+    // This also makes alias order the stored order that `list` numbers by, which `move` can't change.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    sort_on_save: bool,
+    // The order `list` uses when no --sort is passed.
+    #[serde(skip_serializing_if = "SortOrder::is_default")]
+    default_sort: SortOrder,
+    // The fuzzy finder `jump` runs when no alias is given, with any arguments, e.g. `sk` or `peco`. When it isn't
+    // installed, or this is empty, `jump` shows a numbered menu instead.
+    #[serde(skip_serializing_if = "is_default_finder")]
+    finder: String,
+    // How much the closeness of the alias to the query, and the bookmark's frecency, count when `jump` has several
+    // partial matches to choose from (see find_jump_target).
+    #[serde(skip_serializing_if = "is_default_weight")]
+    match_weight: f64,
+    #[serde(skip_serializing_if = "is_default_weight")]
+    frecency_weight: f64,
+    // Like Bookmark::unknown, settings this version doesn't know.
+    #[serde(flatten)]
+    unknown: UnknownFields,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            prune_on_missing: false,
+            sort_on_save: false,
+            default_sort: SortOrder::default(),
+            finder: DEFAULT_FINDER.to_string(),
+            match_weight: DEFAULT_WEIGHT,
+            frecency_weight: DEFAULT_WEIGHT,
+            unknown: BTreeMap::new(),
+        }
+    }
+}
+
+impl Settings {
+    fn is_default(&self) -> bool {
+        *self == Settings::default()
+    }
+
+    // Returns every setting by its key in the [settings] table, including those left at their default, which
+    // serializing the settings leaves out.
+    fn to_table(&self) -> toml::value::Table {
+        let mut table: toml::value::Table =
+            self.unknown.iter().filter_map(|(key, value)| Some((key.clone(), value.clone()?))).collect();
+        table.insert("prune_on_missing".to_string(), toml::Value::Boolean(self.prune_on_missing));
+        table.insert("sort_on_save".to_string(), toml::Value::Boolean(self.sort_on_save));
+        table.insert("default_sort".to_string(), toml::Value::try_from(self.default_sort).expect("Failed to serialize the sort order"));
+        table.insert("finder".to_string(), toml::Value::String(self.finder.clone()));
+        table.insert("match_weight".to_string(), toml::Value::Float(self.match_weight));
+        table.insert("frecency_weight".to_string(), toml::Value::Float(self.frecency_weight));
+        table
+    }
+}
+
+// The defaults of the finder setting and of the two weights of find_jump_target.
+const DEFAULT_FINDER: &str = "fzf";
+const DEFAULT_WEIGHT: f64 = 1.0;
+
+fn is_default_finder(finder: &str) -> bool {
+    finder == DEFAULT_FINDER
+}
+
+fn is_default_weight(weight: &f64) -> bool {
+    *weight == DEFAULT_WEIGHT
+}
+
+#[derive(Debug)]
+enum ConfigError {
+    Read { path: PathBuf, source: io::Error },
+    Parse { path: PathBuf, source: String },
+    UnsupportedVersion { path: PathBuf, version: u32 },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Read { path, source } => {
+                write!(f, "Failed to read config file '{}': {}", path.display(), source)
+            }
+            ConfigError::Parse { path, source } => {
+                write!(f, "Failed to parse config file '{}': {}", path.display(), source)
+            }
+            ConfigError::UnsupportedVersion { path, version } => write!(
+                f,
+                "Config file '{}' has version {}, but this version of pomelo only supports up to {}. Please upgrade pomelo.",
+                path.display(),
+                version,
+                CONFIG_VERSION
+            ),
+        }
+    }
+}
+
+// The file formats the config can be stored in, chosen by the config file's extension.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum ConfigFormat {
+    Toml,
+    Json,
+    Yaml,
+}
+
+impl ConfigFormat {
+    // Picks the format from the path's extension: '.json' is JSON, '.yaml' and '.yml' are YAML, anything else TOML.
+    // The suffixes of the sibling files ('.bak', '.prev', '.tmp') are looked past, so a backup of 'config.json'
+    // is read as JSON too.
+    fn from_path(path: &Path) -> Self {
+        let mut path = path;
+        while matches!(path.extension().and_then(|extension| extension.to_str()), Some("bak" | "prev" | "tmp")) {
+            path = Path::new(path.file_stem().expect("A path with an extension has a file stem"));
+        }
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("json") => ConfigFormat::Json,
+            Some("yaml" | "yml") => ConfigFormat::Yaml,
+            _ => ConfigFormat::Toml,
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            ConfigFormat::Toml => "toml",
+            ConfigFormat::Json => "json",
+            ConfigFormat::Yaml => "yaml",
+        }
+    }
+
+    fn parse(self, contents: &str) -> Result<Config, String> {
+        match self {
+            ConfigFormat::Toml => toml::from_str(contents).map_err(|err| err.to_string()),
+            ConfigFormat::Json => serde_json::from_str(contents).map_err(|err| err.to_string()),
+            ConfigFormat::Yaml => serde_yaml::from_str(contents).map_err(|err| err.to_string()),
+        }
+    }
+
+    fn serialize(self, config: &Config) -> String {
+        match self {
+            // Unknown keys kept by the structs can put a plain value after a table, which TOML can't express. Going
+            // through a toml::Value then writes each table's plain values first, though with the keys sorted, so
+            // it's only done when needed.
+            ConfigFormat::Toml => toml::to_string(config)
+                .or_else(|_| toml::Value::try_from(config).and_then(|value| toml::to_string(&value)))
+                .expect("Failed to serialize the config"),
+            ConfigFormat::Json => serde_json::to_string_pretty(config).expect("Failed to serialize the config") + "\n",
+            ConfigFormat::Yaml => serde_yaml::to_string(config).expect("Failed to serialize the config"),
+        }
+    }
 }
 
+// Shown at the end of `pomelo --help`, since the lookup spans several options and environment variables.
+const CONFIG_HELP: &str = "\
+Config files:
+  Your bookmarks are read from the first of --config, --profile, POMELO_CONFIG and the default location
+  ('pomelo/config.toml' in your config directory). Then the bookmarks of the system config are added after your
+  own, from POMELO_SYSTEM_CONFIG or else '/etc/pomelo/config.toml' ('%ProgramData%\\pomelo\\config.toml' on Windows).
+  Set POMELO_SYSTEM_CONFIG to an empty value to ignore the system config. When both have a bookmark with the same
+  alias, yours takes precedence. System bookmarks are read-only: remove, edit and the other commands that change
+  bookmarks refuse them, but `add --force` with the same alias overrides one with your own. Their on-jump commands
+  are ignored, and jumps to them aren't counted, so they don't gain frecency.";
+
 #[derive(Parser, Debug)]
-#[command(author, version, about, long_about = None)]
+#[command(author, version, about, long_about = None, after_long_help = CONFIG_HELP)]
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+    /// How to report results. With json, add, remove, edit and which print a JSON object, and so do errors.
+    #[arg(long, global = true, value_enum, default_value_t = OutputFormat::Text)]
+    format: OutputFormat,
+    /// Uses this config file instead of the one from POMELO_CONFIG or the default location.
+    #[arg(long, global = true, value_name = "PATH")]
+    config: Option<PathBuf>,
+    /// Uses the bookmarks of a named profile, kept in 'profiles/<NAME>.toml' next to the default config.
+    #[arg(long, global = true, value_name = "NAME", conflicts_with = "config")]
+    profile: Option<String>,
+    /// Also prints the config file in use, resolved paths and what was written, to stderr.
+    #[arg(short, long, global = true, conflicts_with = "quiet")]
+    verbose: bool,
+    /// Only prints results and errors, without messages like "Added bookmark ...".
+    #[arg(short, long, global = true)]
+    quiet: bool,
+    /// Disables colored output. Colors are also off when stdout isn't a terminal or NO_COLOR is set.
+    #[arg(long, global = true)]
+    no_color: bool,
+    /// Prints the config a command would save to stdout instead of writing it, so nothing is changed.
+    #[arg(long, global = true)]
+    dry_run: bool,
+    /// Runs the command and prints its usual messages, but discards the change instead of saving it.
+    #[arg(long, global = true, conflicts_with = "dry_run")]
+    no_save: bool,
+    /// Turns warnings into errors that exit with a non-zero status, for scripts and CI. These are: `add` with a
+    /// directory that's already bookmarked under another alias, `jump` to a bookmark whose path is missing when it
+    /// would fall back to one of its other paths, `list` when no bookmark is listed (like an unknown --tag), `copy`
+    /// without a clipboard tool, a system config that can't be read, and the warnings of `doctor`. Only `import`
+    /// leaving out on-jump commands stays a warning, since leaving them out is the safe choice.
+    #[arg(long, global = true)]
+    strict: bool,
+}
+
+// The ANSI styles used to highlight text output on a terminal.
+#[derive(Clone, Copy, Debug)]
+enum Style {
+    Alias,
+    Path,
+    Success,
+    Warning,
+    Error,
+}
+
+impl Style {
+    fn code(self) -> &'static str {
+        match self {
+            Style::Alias => "1;36",
+            Style::Path => "34",
+            Style::Success => "32",
+            Style::Warning => "1;33",
+            Style::Error => "1;31",
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum OutputFormat {
+    Text,
+    Json,
+}
+
+// How much pomelo says besides the results themselves, set with --quiet and --verbose.
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+enum Verbosity {
+    Quiet,
+    Normal,
+    Verbose,
+}
+
+// How commands write the config, set with the global --format, --quiet, --verbose, --dry-run and --no-save flags.
+// The format is for reporting a config that can't be saved (see check_writable).
+#[derive(Clone, Copy, Debug)]
+struct SaveOptions {
+    format: OutputFormat,
+    verbosity: Verbosity,
+    dry_run: bool,
+    no_save: bool,
+    // Set for `jump`, whose stdout is only for the path the shell function goes to, so the --dry-run preview of
+    // save_config goes to stderr instead.
+    preview_on_stderr: bool,
+}
+
+impl SaveOptions {
+    // Whether changes stay in memory instead of being written, with either --dry-run or --no-save.
+    fn discards_changes(self) -> bool {
+        self.dry_run || self.no_save
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+    #[value(alias = "pwsh")]
+    Powershell,
+}
+
+impl Commands {
+    // Whether the command changes the config file, and so needs to be able to write it (see check_writable).
+    // Commands that only record visits, like `jump`, work on a read-only config too.
+    fn modifies_config(&self) -> bool {
+        match self {
+            Commands::Add { .. }
+            | Commands::BulkAdd { .. }
+            | Commands::Remove { .. }
+            | Commands::Edit { .. }
+            | Commands::Rename { .. }
+            | Commands::Move { .. }
+            | Commands::Touch { .. }
+            | Commands::Pin { .. }
+            | Commands::Unpin { .. }
+            | Commands::Tag { .. }
+            | Commands::Untag { .. }
+            | Commands::Import { .. }
+            | Commands::Convert { .. }
+            | Commands::Restore
+            | Commands::Undo
+            | Commands::Clear { .. }
+            | Commands::Dedup { .. }
+            | Commands::Prune { .. }
+            | Commands::Rebase { .. } => true,
+            Commands::Config { command } => matches!(command, ConfigCommand::Set { .. } | ConfigCommand::Edit),
+            Commands::Jump { create, .. } => *create,
+            Commands::List { .. }
+            | Commands::Recent { .. }
+            | Commands::Search { .. }
+            | Commands::Which { .. }
+            | Commands::LastAdded
+            | Commands::Has { .. }
+            | Commands::Open { .. }
+            | Commands::Copy { .. }
+            | Commands::Tags
+            | Commands::Profiles
+            | Commands::Count { .. }
+            | Commands::Stats { .. }
+            | Commands::Export { .. }
+            | Commands::Sync { .. }
+            | Commands::Doctor
+            | Commands::Init { .. }
+            | Commands::Completions { .. }
+            | Commands::ListAliases { .. } => false,
+        }
+    }
+
+    // Whether the command takes the config lock (see lock_config): the commands that change the config, and `jump`,
+    // which records the visit.
+    fn locks_config(&self) -> bool {
+        self.modifies_config() || matches!(self, Commands::Jump { .. })
+    }
+}
+
+#[derive(Parser, Debug)]
+enum ConfigCommand {
+    /// Prints the value of a setting, e.g. `pomelo config get default_sort`.
+    Get {
+        /// The setting to print: prune_on_missing, sort_on_save, default_sort, finder, match_weight or
+        /// frecency_weight.
+        key: String,
+    },
+    /// Changes a setting, e.g. `pomelo config set default_sort frecency`.
+    Set {
+        /// The setting to change: prune_on_missing, sort_on_save, default_sort, finder, match_weight or
+        /// frecency_weight.
+        key: String,
+        /// The new value, e.g. `true` or `frecency`.
+        value: String,
+    },
+    /// Prints the path of the config file in use, taking --config, --profile and POMELO_CONFIG into account.
+    Path,
+    /// Opens the config file in $VISUAL or $EDITOR, and checks that it can still be read afterwards.
+    ///
+    /// The config from before the edit is kept as the backup, so `pomelo restore` brings it back if the edit
+    /// went wrong.
+    Edit,
+}
+
+
+// The command used by `open` to show a directory in the platform's file manager.
+#[cfg(target_os = "macos")]
+const FILE_MANAGER: &str = "open";
+#[cfg(windows)]
+const FILE_MANAGER: &str = "explorer";
+#[cfg(not(any(target_os = "macos", windows)))]
+const FILE_MANAGER: &str = "xdg-open";
+
+// Where the system config with bookmarks for every user is looked for, unless POMELO_SYSTEM_CONFIG says otherwise.
+#[cfg(not(windows))]
+const SYSTEM_CONFIG_PATH: &str = "/etc/pomelo/config.toml";
+
+// The editor `config edit` falls back to when neither $VISUAL nor $EDITOR is set.
+#[cfg(windows)]
+const DEFAULT_EDITOR: &str = "notepad";
+#[cfg(not(windows))]
+const DEFAULT_EDITOR: &str = "vi";
+
+// The commands `copy` tries in turn to put text on the clipboard, each reading the text from stdin.
+#[cfg(target_os = "macos")]
+const CLIPBOARD_COMMANDS: &[(&str, &[&str])] = &[("pbcopy", &[])];
+#[cfg(windows)]
+const CLIPBOARD_COMMANDS: &[(&str, &[&str])] = &[("clip", &[])];
+#[cfg(not(any(target_os = "macos", windows)))]
+const CLIPBOARD_COMMANDS: &[(&str, &[&str])] =
+    &[("wl-copy", &[]), ("xclip", &["-selection", "clipboard"]), ("xsel", &["--clipboard", "--input"])];
+
+#[derive(ValueEnum, Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum SortOrder {
+    /// Alphabetically by alias.
+    Alias,
+    /// Alphabetically by path.
+    Path,
+    /// In the order the bookmarks were added.
+    #[default]
+    Added,
+    /// Most frequently and recently jumped-to bookmarks first.
+    Frecency,
+}
+
+impl SortOrder {
+    fn is_default(&self) -> bool {
+        *self == SortOrder::default()
+    }
+}
+
+// What `dedup` compares bookmarks by.
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum DedupKey {
+    /// Bookmarks with the same alias.
+    Alias,
+    /// Bookmarks pointing at the same directory, compared with symlinks resolved.
+    Path,
+}
+
+// Where `import` reads bookmarks from.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum ImportSource {
+    /// A TOML file written by `export`.
+    Pomelo,
+    /// zoxide's database, 'db.zo'.
+    Zoxide,
+    /// autojump's database, 'autojump.txt'.
+    Autojump,
 }
 
 #[derive(Parser, Debug)]
 enum Commands {
-    /// Creates a bookmark for the current directory.
+    /// Creates a bookmark for the current directory, or for the directory given with --path.
     Add {
-        /// The alias for the current directory.
-        #[arg(short, long, required = true)]
-        alias: String,
+        /// The alias for the directory. Defaults to the directory's name.
+        #[arg(short, long)]
+        alias: Option<String>,
+        /// The directory to bookmark, or a file (see --file). Defaults to the current directory.
+        #[arg(short, long)]
+        path: Option<PathBuf>,
+        /// Points an existing bookmark with the same alias at the directory instead of failing.
+        #[arg(short, long, visible_alias = "overwrite")]
+        force: bool,
+        /// Saves the bookmark even if the directory doesn't exist right now (e.g. an unmounted drive).
+        #[arg(long)]
+        allow_missing: bool,
+        /// If the directory is already bookmarked under another alias, renames that bookmark instead of adding a new one.
+        #[arg(short, long)]
+        update: bool,
+        /// Keeps the path as given instead of resolving symlinks to the real directory, which is the default.
+        #[arg(long)]
+        no_canonicalize: bool,
+        /// Tags the bookmark, e.g. `--tag work`. Can be repeated.
+        #[arg(short, long = "tag")]
+        tags: Vec<String>,
+        /// A note on what the bookmark is for, shown by `list --long`.
+        #[arg(short, long)]
+        description: Option<String>,
+        /// A shell command to run after jumping to the bookmark, e.g. `source .venv/bin/activate`.
+        /// The shell functions from `init` run it as is in your shell, so only use commands you trust.
+        #[arg(long, value_name = "COMMAND")]
+        on_jump: Option<String>,
+        /// Adds the path to the existing bookmark with this alias, as another place `jump` looks for it.
+        /// The bookmark then goes to the first of its paths that exists, e.g. for a project kept in different
+        /// places on different machines.
+        #[arg(long, conflicts_with_all = ["force", "update"])]
+        append: bool,
+        /// Saves the path as given, e.g. `'$PROJECT_ROOT/service'`, and expands its environment variables on every
+        /// jump instead of once now. Quote the path so your shell doesn't expand it first.
+        #[arg(long, requires = "path")]
+        expand_on_jump: bool,
+        /// Bookmarks the file at --path. This is also assumed when the path is an existing file, but is needed
+        /// with --allow-missing for a file that doesn't exist yet.
+        #[arg(long, requires = "path")]
+        file: bool,
+    },
+    /// Adds many bookmarks at once, from 'alias<TAB>path' or 'alias=path' lines.
+    ///
+    /// Empty lines and lines starting with '#' are ignored. Aliases you already have are skipped, and lines that
+    /// can't be added are reported without stopping the rest.
+    BulkAdd {
+        /// The file to read the lines from. Reads stdin when omitted or '-'.
+        file: Option<PathBuf>,
+        /// Adds bookmarks for directories that don't exist right now too.
+        #[arg(long)]
+        allow_missing: bool,
     },
     /// Removes a bookmark.
     Remove {
-        /// The alias you want to remove.
-        #[arg(short, long, required = true)]
-        alias: String,
+        /// The aliases you want to remove, e.g. `-a docs -a music` or `-a docs music`.
+        #[arg(short, long = "alias", value_name = "ALIAS", required = true, num_args = 1..)]
+        aliases: Vec<String>,
+        /// Matches the alias ignoring ASCII case.
+        #[arg(short, long)]
+        ignore_case: bool,
     },
     /// Lists all your bookmarks.
-    List,
+    List {
+        /// The order to list bookmarks in. Defaults to the default_sort setting, which is `added` unless changed.
+        #[arg(short, long, value_enum)]
+        sort: Option<SortOrder>,
+        /// Reverses the chosen order.
+        #[arg(short, long)]
+        reverse: bool,
+        /// Prints the bookmarks as a JSON array instead. Implied by --format json.
+        #[arg(long)]
+        json: bool,
+        /// Also shows when each bookmark was created, its tags and its description.
+        #[arg(short, long)]
+        long: bool,
+        /// Only lists bookmarks with this tag.
+        #[arg(short, long)]
+        tag: Option<String>,
+        /// Only lists bookmarks in this namespace, e.g. `--group work` for `work/api` and `work/web`.
+        #[arg(short, long, value_name = "NAMESPACE")]
+        group: Option<String>,
+        /// Only lists bookmarks whose directory no longer exists.
+        #[arg(long)]
+        only_broken: bool,
+        /// Only lists bookmarks added at or after this time: a date like `2024-05-01` (in local time), a time like
+        /// `2024-05-01T09:00:00Z`, or a duration ago like `30d` (m, h, d, w, mo or y). Bookmarks from before the
+        /// creation time was recorded are left out.
+        #[arg(long, value_name = "TIME", value_parser = |time: &str| parse_time(time, false))]
+        since: Option<DateTime<Utc>>,
+        /// Only lists bookmarks added before this time, in the same formats as --since. A date includes that day.
+        #[arg(long, value_name = "TIME", value_parser = |time: &str| parse_time(time, true))]
+        until: Option<DateTime<Utc>>,
+        /// Only lists bookmarks whose alias matches this glob, e.g. `'proj-*'`. The whole alias has to match, so use
+        /// `'*docs*'` to find it anywhere. `*` matches any run of characters and `?` a single one.
+        #[arg(short, long, value_name = "PATTERN")]
+        r#match: Option<String>,
+        /// Shows paths under your home directory as '~/...'. The stored paths stay absolute.
+        #[arg(long)]
+        relative: bool,
+        /// Only lists the first N bookmarks after sorting, e.g. `--sort frecency --limit 5`. 0 lists all of them.
+        #[arg(long, value_name = "N", default_value_t = 0)]
+        limit: usize,
+        /// Prints each bookmark as this template, e.g. `'{alias}: {path}'`, without the heading.
+        /// Placeholders: {index}, {alias}, {path}, {name} (the directory's name, like `which --name-only`), {visits},
+        /// {last_visited}, {created_at}, {tags} and {description}.
+        /// Write `{{` and `}}` for literal braces.
+        #[arg(long, value_name = "TEMPLATE", conflicts_with_all = ["json", "long"])]
+        format_string: Option<String>,
+    },
+    /// Lists the bookmarks you jumped to most recently, newest first.
+    Recent {
+        /// How many bookmarks to list.
+        #[arg(short, long, default_value_t = 10)]
+        limit: usize,
+        /// Prints the bookmarks as a JSON array instead. Implied by --format json.
+        #[arg(long)]
+        json: bool,
+    },
+    /// Lists the bookmarks whose alias, path or description contains the query, ignoring case.
+    Search {
+        /// The text to look for.
+        query: String,
+        /// Only searches aliases.
+        #[arg(short, long, conflicts_with = "path_only")]
+        alias_only: bool,
+        /// Only searches paths.
+        #[arg(short, long)]
+        path_only: bool,
+    },
     /// Edits an existing bookmark.
+    #[command(group(ArgGroup::new("changes").required(true).multiple(true).args(["new", "path", "description", "on_jump"])))]
     Edit {
         /// The alias of the bookmark you want to edit.
         #[arg(short, long, required = true)]
         alias: String,
         /// The new alias for the bookmark.
+        #[arg(short, long)]
+        new: Option<String>,
+        /// The new directory for the bookmark.
+        #[arg(short, long)]
+        path: Option<PathBuf>,
+        /// Accepts a new directory that doesn't exist right now (e.g. an unmounted drive).
+        #[arg(long, requires = "path")]
+        allow_missing: bool,
+        /// The new description for the bookmark. An empty one removes it.
+        #[arg(short, long)]
+        description: Option<String>,
+        /// The new shell command to run after jumping to the bookmark (see `add --on-jump`). An empty one removes it.
+        #[arg(long, value_name = "COMMAND")]
+        on_jump: Option<String>,
+        /// Matches the alias ignoring ASCII case.
+        #[arg(short, long)]
+        ignore_case: bool,
+    },
+    /// Renames a bookmark, leaving its path untouched.
+    Rename {
+        /// The alias of the bookmark you want to rename.
+        #[arg(short, long, required = true)]
+        alias: String,
+        /// The new alias for the bookmark.
+        #[arg(short, long, required = true)]
+        new: String,
+        /// Replaces an existing bookmark that already uses the new alias.
+        #[arg(short, long)]
+        force: bool,
+    },
+    /// Moves a bookmark to another position in the stored order, shifting the ones in between.
+    Move {
+        /// The alias of the bookmark you want to move.
         #[arg(short, long, required = true)]
-        new: String
+        alias: String,
+        /// The new position, counting from 1 like the numbers shown by `list`.
+        #[arg(short, long, required = true)]
+        to: usize,
     },
     /// Jumps to a bookmark (directory).
+    ///
+    /// A program can't change the directory of the shell that started it, so `jump` prints the
+    /// bookmarked path to stdout and leaves the `cd` to a small shell function. A bookmark's
+    /// on-jump command follows on a second line, for that function to run after the `cd`.
+    /// `pomelo init` prints the function for your shell. Add it to your rc file, e.g.
+    ///
+    ///     eval "$(pomelo init bash)"
+    ///
+    /// and use `j <alias>` to jump.
+    #[command(verbatim_doc_comment)]
+    #[command(group(ArgGroup::new("target").args(["alias", "index", "ancestor"])))]
     Jump {
-        /// The bookmark you want to jump to.
+        /// The bookmark you want to jump to. Falls back to the best partial match when no alias matches exactly,
+        /// ranked by pinning and the match_weight and frecency_weight settings (1 each by default), unless another
+        /// one comes close. When omitted in a terminal, you pick the bookmark with fzf (see the finder setting), or
+        /// from a menu without it. `-` goes back like --back.
+        #[arg(short, long)]
+        alias: Option<String>,
+        /// The number of the bookmark you want to jump to, as shown by `list`.
+        #[arg(long, value_name = "N")]
+        index: Option<usize>,
+        /// Jumps to the nearest bookmark above the current directory, e.g. the root of the project you're in.
+        #[arg(long)]
+        ancestor: bool,
+        /// Jumps to the parent of the bookmarked directory instead.
+        #[arg(short, long, conflicts_with = "subdir")]
+        parent: bool,
+        /// Leaves out the bookmark's --on-jump command.
+        #[arg(long)]
+        no_hooks: bool,
+        /// Removes the bookmark if its directory no longer exists. Set `prune_on_missing = true` under [settings]
+        /// in the config to always do this.
+        #[arg(long)]
+        prune_missing: bool,
+        /// Goes back to the directory you were in before the last jump, like `cd -`.
+        #[arg(short, long, conflicts_with_all = ["target", "subdir"])]
+        back: bool,
+        /// Only print the path, without the shell setup hint shown on a terminal. Implied by --quiet.
+        #[arg(long)]
+        print_path: bool,
+        /// Prints the path quoted for a POSIX shell, e.g. for `eval "cd $(pomelo jump --shell-escape ...)"`.
+        /// Not needed with `"$(...)"`, which is how the shell functions from `init` use it.
+        #[arg(long)]
+        shell_escape: bool,
+        /// Only jump on an exact alias match.
+        #[arg(short, long)]
+        exact: bool,
+        /// Matches the alias ignoring ASCII case.
+        #[arg(short, long)]
+        ignore_case: bool,
+        /// A directory inside the bookmark to land in, e.g. `src/handlers`.
+        #[arg(short, long)]
+        subdir: Option<PathBuf>,
+        /// When no bookmark matches the alias, bookmarks the directory of that name under the current directory,
+        /// or the one given with --path, and jumps there.
+        #[arg(long, requires = "alias")]
+        create: bool,
+        /// The directory --create bookmarks, instead of the one named like the alias.
+        #[arg(long, requires = "create")]
+        path: Option<PathBuf>,
+        /// For a bookmark of a file, also opens the file with its default application, like `open` does.
+        #[arg(long, conflicts_with = "back")]
+        open_file: bool,
+    },
+    /// Prints the path of a bookmark, without jumping to it.
+    #[command(visible_alias = "resolve")]
+    Which {
+        /// The bookmark you want the path of.
         #[arg(short, long, required = true)]
-        alias: String
-    }
+        alias: String,
+        /// Prints the path quoted for a POSIX shell.
+        #[arg(long)]
+        shell_escape: bool,
+        /// Prints the real path, with symlinks resolved, instead of the stored one. Fails if it doesn't exist.
+        #[arg(long)]
+        canonical: bool,
+        /// Only prints the name of the directory, e.g. 'api' for '/home/me/projects/api'. A path without one,
+        /// like '/', is printed in full.
+        #[arg(long)]
+        name_only: bool,
+    },
+    /// Prints the path of the bookmark added most recently, e.g. to use it in a script right after `add`.
+    LastAdded,
+    /// Checks whether a bookmark exists, for scripts: `if pomelo has -a docs; then ...`.
+    ///
+    /// Exits with status 0 if it does and 1 otherwise, without printing anything. With --verbose, the bookmark's
+    /// path is printed when it exists.
+    Has {
+        /// The alias to look for.
+        #[arg(short, long, required = true)]
+        alias: String,
+    },
+    /// Opens a bookmark in your file manager, or a file bookmark with its default application.
+    Open {
+        /// The bookmark you want to open.
+        #[arg(short, long, required = true)]
+        alias: String,
+    },
+    /// Counts a visit to a bookmark without jumping to it, so it ranks higher by frecency.
+    #[command(visible_alias = "visit")]
+    Touch {
+        /// The bookmark you visited.
+        #[arg(short, long, required = true)]
+        alias: String,
+    },
+    /// Copies a bookmark's path to the clipboard.
+    ///
+    /// Uses pbcopy on macOS, clip on Windows, and wl-copy, xclip or xsel elsewhere. Without any of them, the path is
+    /// printed instead.
+    Copy {
+        /// The bookmark whose path you want to copy.
+        #[arg(short, long, required = true)]
+        alias: String,
+    },
+    /// Pins a bookmark, so it's listed before the others.
+    Pin {
+        /// The bookmark you want to pin.
+        #[arg(short, long, required = true)]
+        alias: String,
+    },
+    /// Unpins a bookmark.
+    Unpin {
+        /// The bookmark you want to unpin.
+        #[arg(short, long, required = true)]
+        alias: String,
+    },
+    /// Adds tags to bookmarks, and removes them, e.g. `pomelo tag -a api web --add work --remove old`.
+    Tag {
+        /// The bookmarks to change, e.g. `-a docs -a music` or `-a docs music`.
+        #[arg(short, long = "alias", value_name = "ALIAS", num_args = 1.., required_unless_present = "match")]
+        aliases: Vec<String>,
+        /// Also changes every bookmark whose alias matches this glob, e.g. `'work/*'` (see `list --match`).
+        #[arg(short, long, value_name = "PATTERN")]
+        r#match: Option<String>,
+        /// A tag to add. Can be repeated.
+        #[arg(long, value_name = "TAG", required_unless_present = "remove")]
+        add: Vec<String>,
+        /// A tag to remove. Can be repeated.
+        #[arg(long, value_name = "TAG")]
+        remove: Vec<String>,
+    },
+    /// Removes tags from bookmarks, like `tag --remove`, e.g. `pomelo untag -a docs old`.
+    Untag {
+        /// A bookmark to change. Can be repeated, e.g. `-a docs -a music`.
+        #[arg(short, long = "alias", value_name = "ALIAS", required_unless_present = "match")]
+        aliases: Vec<String>,
+        /// Also changes every bookmark whose alias matches this glob, e.g. `'work/*'` (see `list --match`).
+        #[arg(short, long, value_name = "PATTERN")]
+        r#match: Option<String>,
+        /// The tags to remove.
+        #[arg(required = true)]
+        tags: Vec<String>,
+    },
+    /// Lists every tag used by your bookmarks.
+    Tags,
+    /// Lists your profiles. The one in use is marked with '*'.
+    Profiles,
+    /// Prints the number of bookmarks.
+    Count {
+        /// Only counts bookmarks with this tag.
+        #[arg(short, long)]
+        tag: Option<String>,
+    },
+    /// Summarizes how you use your bookmarks.
+    Stats {
+        /// Prints the summary as a JSON object instead. Implied by --format json.
+        #[arg(long)]
+        json: bool,
+    },
+    /// Writes all your bookmarks to a TOML file, or to stdout when no file is given.
+    Export {
+        /// The file to write the bookmarks to.
+        file: Option<PathBuf>,
+        /// Writes one JSON object per bookmark per line instead, for piping into tools like jq.
+        #[arg(long)]
+        jsonl: bool,
+    },
+    /// Reads bookmarks from a TOML file written by `export`, replacing your current bookmarks.
+    ///
+    /// With --from zoxide or --from autojump, the directories in that tool's database are added to your
+    /// bookmarks instead, named after the directory. Directories that no longer exist are skipped.
+    Import {
+        /// The file to read the bookmarks from. Defaults to the database's usual location with --from zoxide or autojump.
+        #[arg(required_unless_present = "from")]
+        file: Option<PathBuf>,
+        /// The kind of file to import.
+        #[arg(long, value_enum, default_value_t = ImportSource::Pomelo)]
+        from: ImportSource,
+        /// Adds the imported bookmarks to your current ones instead. Aliases you already have are skipped.
+        /// Always the case with --from zoxide or autojump.
+        #[arg(short, long)]
+        merge: bool,
+        /// When merging, lets imported bookmarks overwrite existing ones with the same alias.
+        #[arg(short, long)]
+        force: bool,
+        /// Keeps the on-jump commands of the imported bookmarks. The `j` function runs them in your shell, so
+        /// they're left out by default, and only listed, in case the file came from someone else.
+        #[arg(long)]
+        allow_hooks: bool,
+    },
+    /// Commits your config file, when it's kept in a git repository (e.g. your dotfiles).
+    Sync {
+        /// Also pushes the commit to the repository's upstream.
+        #[arg(long)]
+        push: bool,
+    },
+    /// Rewrites your config in another file format, next to the current file, and removes the old file.
+    ///
+    /// Its backup for `restore` and snapshot for `undo` are converted along with it. The format is picked by the config file's extension, so point POMELO_CONFIG or --config at the new file if
+    /// you use either. The default location and profiles pick up the converted file on their own.
+    Convert {
+        /// The format to convert the config to.
+        #[arg(long, value_enum)]
+        to: ConfigFormat,
+    },
+    /// Reads or changes the settings kept in your config's [settings] table.
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+    /// Checks your config for problems: whether it can be read, broken or duplicate bookmarks, and file permissions.
+    ///
+    /// Exits with a non-zero status if any check fails, or with --strict, if any check warns.
+    #[command(visible_alias = "check")]
+    Doctor,
+    /// Swaps the config with the backup taken before the last `remove`, `import`, `clear`, `dedup`, `prune`,
+    /// `rebase` or `config edit`.
+    ///
+    /// The backup is 'config.toml.bak' next to your config file. Since the two are swapped,
+    /// running `restore` again undoes the restore.
+    Restore,
+    /// Reverts the last change to your bookmarks. Only one level of undo is kept.
+    Undo,
+    /// Removes all bookmarks, after asking for confirmation. The config is backed up first, see `restore`.
+    Clear {
+        /// Doesn't ask for confirmation. Required when stdin isn't a terminal.
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// Removes duplicate bookmarks, keeping one of each group: the pinned one, or else the most visited one, or else
+    /// the one added first.
+    Dedup {
+        /// What makes two bookmarks duplicates.
+        #[arg(long, value_enum, default_value_t = DedupKey::Path)]
+        by: DedupKey,
+    },
+    /// Removes bookmarks whose directories no longer exist.
+    ///
+    /// With --dry-run, only reports what would be removed, without changing anything.
+    Prune {
+        /// Also removes bookmarks last jumped to longer ago than this, e.g. `30d`, `6mo` or `1y`. Bookmarks that
+        /// were never jumped to count from when they were added.
+        #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+        older_than: Option<chrono::Duration>,
+    },
+    /// Points the bookmarks under a directory that was moved at its new location, e.g. `pomelo rebase ~/code ~/dev`.
+    ///
+    /// Every bookmark whose path, or one of its other paths, is in FROM gets the same path in TO instead. The new
+    /// paths have to exist, unless --allow-missing is passed. With --dry-run, only reports what would change.
+    #[command(visible_alias = "rename-path-prefix")]
+    Rebase {
+        /// The directory the bookmarked paths were in.
+        from: PathBuf,
+        /// The directory they're in now.
+        to: PathBuf,
+        /// Rewrites the paths even when what they now point at doesn't exist.
+        #[arg(long)]
+        allow_missing: bool,
+    },
+    /// Prints the shell integration code that defines the `j` function.
+    ///
+    /// Add the matching line to your shell's rc file:
+    ///
+    ///     bash:       eval "$(pomelo init bash)"
+    ///     zsh:        eval "$(pomelo init zsh)"
+    ///     fish:       pomelo init fish | source
+    ///     powershell: Invoke-Expression (& pomelo init powershell | Out-String)
+    #[command(verbatim_doc_comment)]
+    Init {
+        /// The shell to generate code for. Detected from $SHELL when omitted.
+        shell: Option<Shell>,
+    },
+    /// Prints a completion script for pomelo's subcommands and flags.
+    ///
+    /// In bash, zsh and fish, aliases are completed from your bookmarks too.
+    /// Save it where your shell looks for completions:
+    ///
+    ///     bash:       pomelo completions bash > ~/.local/share/bash-completion/completions/pomelo
+    ///     zsh:        pomelo completions zsh > ~/.zfunc/_pomelo  (with ~/.zfunc in your $fpath)
+    ///     fish:       pomelo completions fish > ~/.config/fish/completions/pomelo.fish
+    ///     powershell: pomelo completions powershell >> $PROFILE
+    #[command(verbatim_doc_comment)]
+    Completions {
+        /// The shell to generate completions for.
+        shell: Shell,
+    },
+    /// Prints every alias, one per line. Used by the shell completions.
+    #[command(hide = true)]
+    ListAliases {
+        /// Only prints the completions of this prefix, with namespaces beyond it shortened to e.g. 'work/'.
+        #[arg(long, value_name = "PREFIX")]
+        complete: Option<String>,
+    },
 }
 
-// Attempts to load the configuration from a predefined path.
+// Attempts to load the configuration from the given path (see get_config_path).
 // If the configuration file exists and is valid, it reads the file and deserializes the TOML into a Config struct.
-// If the file doesn't exist or an error occurs while reading, it returns a new Config struct with an empty bookmarks vector.
-fn load_or_initialize_config() -> Config {
-    let config_path = get_config_path();
-    match fs::read_to_string(&config_path) {
-        Ok(contents) => toml::from_str(&contents).unwrap(),
-        Err(_) => Config { bookmarks: Vec::new() },
+// If the file doesn't exist, it returns a new Config struct with an empty bookmarks vector.
+// Any other read error, or invalid TOML, is returned as a ConfigError so existing bookmarks are never silently discarded.
+// Configs in an older format are migrated to the current one and saved right away.
+// The bookmarks of the system config are then merged in under the user's own (see merge_system_config), where
+// `strict` makes a system config that can't be read an error too.
+fn load_or_initialize_config(config_path: &Path, options: SaveOptions, strict: bool) -> Result<Config, ConfigError> {
+    let mut config = match read_config(config_path) {
+        Err(ConfigError::Read { source, .. }) if source.kind() == io::ErrorKind::NotFound => {
+            let mut config = Config { version: CONFIG_VERSION, settings: Settings::default(), bookmarks: Vec::new(), unknown: BTreeMap::new() };
+            merge_system_config(&mut config, strict)?;
+            return Ok(config);
+        }
+        result => result?,
+    };
+
+    if migrate_config(&mut config) {
+        trace(options.verbosity, format!("Migrated '{}' to config version {}", config_path.display(), CONFIG_VERSION));
+        // A read-only config can still be used as it is, so the upgrade is saved some other time.
+        if check_writable(config_path).is_ok() {
+            write_config(&config, config_path, options);
+        }
     }
+    merge_system_config(&mut config, strict)?;
+    Ok(config)
 }
 
-// Takes a reference to a Config struct and serializes it into TOML format.
-// It then writes this serialized TOML string to a file at the location specified by get_config_path.
-// If the file doesn't exist, it creates a new one. If the directory doesn't exist, it creates a new directory.
-// If any operation fails, the function panics with an appropriate message.
-fn save_config(config: &Config) {
-    let config_path = get_config_path();
-    let config_dir = config_path.parent().expect("Failed to get config directory path");
+// Returns where the system config lives: POMELO_SYSTEM_CONFIG if it's set, where an empty value turns the system
+// config off, and otherwise '/etc/pomelo/config.toml', or 'pomelo\config.toml' under %ProgramData% on Windows.
+// An admin can put default bookmarks there for everyone on a shared machine.
+fn system_config_path() -> Option<PathBuf> {
+    if let Some(path) = env::var_os("POMELO_SYSTEM_CONFIG") {
+        return (!path.is_empty()).then(|| PathBuf::from(path));
+    }
+    #[cfg(windows)]
+    return env::var_os("ProgramData").map(|dir| PathBuf::from(dir).join("pomelo").join("config.toml"));
+    #[cfg(not(windows))]
+    Some(PathBuf::from(SYSTEM_CONFIG_PATH))
+}
 
-    if !config_dir.exists() {
-        fs::create_dir_all(config_dir).expect("Failed to create config directory");
+// Adds the bookmarks of the system config (see system_config_path) after the user's own, marked as system
+// bookmarks. The user's config takes precedence: a system bookmark whose alias the user also has is left out.
+// Their on-jump commands are dropped, since POMELO_SYSTEM_CONFIG may point at a file someone else wrote.
+// The system config is optional, so if it can't be read, that's only a warning, unless `strict` (--strict) makes
+// it an error.
+fn merge_system_config(config: &mut Config, strict: bool) -> Result<(), ConfigError> {
+    let Some(path) = system_config_path() else {
+        return Ok(());
+    };
+    let system = match read_config(&path) {
+        Ok(system) => system,
+        Err(ConfigError::Read { source, .. }) if source.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(err) if strict => return Err(err),
+        Err(err) => {
+            eprintln!("Warning: ignoring the system config. {}", err);
+            return Ok(());
+        }
+    };
+    for mut bookmark in system.bookmarks {
+        if !config.bookmarks.iter().any(|b| b.alias == bookmark.alias) {
+            // On-jump commands are run in the user's shell, so they only ever come from the user's own config.
+            bookmark.on_jump = None;
+            bookmark.system = true;
+            config.bookmarks.push(bookmark);
+        }
     }
+    Ok(())
+}
 
-    let toml = toml::to_string(config).expect("Failed to serialize the config");
-    let mut file = File::create(&config_path).expect("Failed to create config file");
-    file.write_all(toml.as_bytes()).expect("Failed to write to config file");
+// Returns the config as it's written to the file: without the bookmarks merged in from the system config, and with
+// the bookmarks sorted by alias when the sort_on_save setting is on.
+fn stored_config(config: &Config) -> Cow<'_, Config> {
+    if !config.settings.sort_on_save && !config.bookmarks.iter().any(|b| b.system) {
+        return Cow::Borrowed(config);
+    }
+    let mut stored = config.clone();
+    stored.bookmarks.retain(|b| !b.system);
+    if stored.settings.sort_on_save {
+        stored.bookmarks.sort_by(|a, b| a.alias.cmp(&b.alias));
+    }
+    Cow::Owned(stored)
 }
 
-// Constructs and returns the path to the configuration file.
-// It determines the user's home directory using the dirs crate and appends the relative path to the 'config.toml' file within the '.pomelo' directory.
-// This function panics if it fails to determine the home directory.
-fn get_config_path() -> PathBuf {
-    let home_dir = dirs::home_dir().expect("Failed to find home directory");
-    home_dir.join(".pomelo").join("config.toml")
+// Upgrades a config loaded from an older format to the current CONFIG_VERSION.
+// Returns whether anything changed, so the caller knows to save the upgraded config.
+fn migrate_config(config: &mut Config) -> bool {
+    if config.version == CONFIG_VERSION {
+        return false;
+    }
+
+    // Version 0 is the unversioned format. Its bookmarks already have the version 1 shape, with any fields added
+    // since filled in by serde defaults, so only the version needs recording.
+    if config.version == 0 {
+        config.version = 1;
+    }
+    true
 }
 
-fn main() {
-    let cli = Cli::parse();
+// Looks up a setting by its key in the [settings] table, with the value it has there.
+fn get_setting(settings: &Settings, key: &str) -> Result<toml::Value, String> {
+    settings.to_table().get(key).cloned().ok_or_else(|| format!("Unknown setting '{}'", key))
+}
 
-    let mut config = load_or_initialize_config();
+// Changes a setting by its key in the [settings] table. The value is read as a boolean or a number where it looks
+// like one, and as a string otherwise, and then has to fit the setting's type.
+fn set_setting(settings: &mut Settings, key: &str, value: &str) -> Result<(), String> {
+    let mut table = settings.to_table();
+    if !table.contains_key(key) {
+        return Err(format!("Unknown setting '{}'", key));
+    }
+    let parsed = match (value.parse::<bool>(), value.parse::<i64>(), value.parse::<f64>()) {
+        (Ok(value), _, _) => toml::Value::Boolean(value),
+        (_, Ok(value), _) => toml::Value::Integer(value),
+        (_, _, Ok(value)) => toml::Value::Float(value),
+        _ => toml::Value::String(value.to_string()),
+    };
+    table.insert(key.to_string(), parsed);
+    *settings = toml::Value::Table(table)
+        .try_into()
+        .map_err(|err| format!("Invalid value '{}' for '{}': {}", value, key, err))?;
+    Ok(())
+}
 
-    match &cli.command {
-        Commands::Add { alias } => {
-            let current_dir = env::current_dir().expect("Failed to get current directory");
-            let bookmark = Bookmark {
-                alias: alias.clone(),
-                path: current_dir,
-            };
-            config.bookmarks.push(bookmark);
-            save_config(&config);
-            println!("Added bookmark with alias '{}'", alias);
-        }
-        Commands::Remove { alias } => {
-           if let Some(index) = config.bookmarks.iter().position(|bookmark| bookmark.alias == *alias) {
-            config.bookmarks.remove(index);
-            println!("Removed bookmark with alias '{}'", alias);
-           } else {
-            println!("No bookmark found with alias '{}'", alias);
-           }
+// Reads the file at the given path and deserializes it into a Config struct, as TOML, JSON or YAML depending on
+// its extension (see ConfigFormat::from_path).
+// Used for the user's own config as well as for files passed to `import`.
+// Files written by a newer pomelo with a config version this build doesn't know are rejected.
+fn read_config(path: &Path) -> Result<Config, ConfigError> {
+    let contents = fs::read_to_string(path).map_err(|source| ConfigError::Read { path: path.to_path_buf(), source })?;
+    let config = ConfigFormat::from_path(path)
+        .parse(&contents)
+        .map_err(|source| ConfigError::Parse { path: path.to_path_buf(), source })?;
+    if config.version > CONFIG_VERSION {
+        return Err(ConfigError::UnsupportedVersion { path: path.to_path_buf(), version: config.version });
+    }
+    Ok(config)
+}
 
-           save_config(&config)
+// Saves the config after a change the user may want to undo.
+// Before writing, the current config file is copied to the undo snapshot (see get_undo_path), so `undo` can bring
+// back the state from before this change.
+// With --dry-run, the config that would have been written is printed to stdout instead (stderr for `jump`), and
+// nothing is touched. With --no-save, the change is just discarded.
+fn save_config(config: &Config, config_path: &Path, options: SaveOptions) {
+    if options.dry_run {
+        let preview = ConfigFormat::from_path(config_path).serialize(&stored_config(config));
+        if options.preview_on_stderr {
+            eprint!("{}", preview);
+        } else {
+            print!("{}", preview);
         }
-        Commands::Edit { alias, new } => {
-            if let Some(bookmark) = config.bookmarks.iter_mut().find(|b| b.alias == *alias) {
-                bookmark.alias = new.clone();
-                println!("Updated alias '{}' to '{}'", alias, new);
-            } else {
-                println!("No bookmark found with alias '{}'", alias);
-            }
-        
-            save_config(&config);
+        eprintln!("Dry run: no changes were written to '{}'", config_path.display());
+        return;
+    }
+    if options.no_save {
+        eprintln!("Not saved: the change was discarded because of --no-save.");
+        return;
+    }
+    if let Err(err) = check_writable(config_path) {
+        fail(options.format, err);
+    }
+    if config_path.exists() {
+        fs::copy(config_path, get_undo_path(config_path)).expect("Failed to save undo snapshot");
+    }
+    write_config(config, config_path, options);
+}
+
+// Takes a reference to a Config struct and serializes it in the format matching the config path's extension.
+// It then writes the serialized config to a temporary file next to the given config path,
+// and renames it over the real file. The rename is atomic on the same filesystem, so a crash or a full disk
+// mid-write leaves the previous config intact instead of a truncated one.
+// If the directory doesn't exist, it creates a new directory.
+// If any operation fails, the function panics with an appropriate message.
+// Unlike save_config, this doesn't touch the undo snapshot, which is what bookkeeping like visit counts wants.
+// With the sort_on_save setting, the bookmarks are written sorted by alias.
+// With --dry-run or --no-save, nothing is written.
+fn write_config(config: &Config, config_path: &Path, options: SaveOptions) {
+    if options.discards_changes() {
+        trace(options.verbosity, format!("Skipped writing '{}'", config_path.display()));
+        return;
+    }
+    let config_dir = config_path.parent().expect("Failed to get config directory path");
+    // A directory that can't be created is reported by check_writable.
+    if !config_dir.as_os_str().is_empty() && !config_dir.exists() {
+        let _ = create_config_dir(config_dir);
+    }
+    if let Err(err) = check_writable(config_path) {
+        fail(options.format, err);
+    }
+
+    let config = stored_config(config);
+    let contents = ConfigFormat::from_path(config_path).serialize(&config);
+    let tmp_path = with_suffix(config_path, ".tmp");
+    let mut file = File::create(&tmp_path).expect("Failed to create temporary config file");
+    // Bookmark paths can reveal a lot about someone's projects, so only the owner may read the file.
+    #[cfg(unix)]
+    file.set_permissions(fs::Permissions::from_mode(0o600)).expect("Failed to set config file permissions");
+    file.write_all(contents.as_bytes()).expect("Failed to write to temporary config file");
+    file.sync_all().expect("Failed to flush temporary config file");
+    fs::rename(&tmp_path, config_path).expect("Failed to replace config file");
+    trace(options.verbosity, format!("Wrote {} bookmark(s) to '{}'", config.bookmarks.len(), config_path.display()));
+}
+
+// Creates the directory holding the config file, including any missing parents.
+// On Unix the directory itself is made private to its owner (mode 0700). Existing directories are never passed in,
+// so pointing POMELO_CONFIG into a shared directory doesn't change that directory's permissions.
+fn create_config_dir(config_dir: &Path) -> io::Result<()> {
+    fs::create_dir_all(config_dir)?;
+    #[cfg(unix)]
+    fs::set_permissions(config_dir, fs::Permissions::from_mode(0o700))?;
+    Ok(())
+}
+
+// Whether an IO error means the file or directory can't be written, as opposed to something going wrong.
+fn is_read_only_error(err: &io::Error) -> bool {
+    matches!(err.kind(), io::ErrorKind::PermissionDenied | io::ErrorKind::ReadOnlyFilesystem)
+}
+
+// Checks that the config can be saved, before anything is changed: an existing config file must not be read-only,
+// and its directory has to allow creating the temporary file write_config replaces it with. This is common when
+// the config is on a read-only mount or owned by root. Returns an error explaining which path can't be written.
+fn check_writable(config_path: &Path) -> Result<(), String> {
+    if config_path.metadata().is_ok_and(|metadata| metadata.permissions().readonly()) {
+        return Err(format!(
+            "'{}' is read-only, so your changes can't be saved. Make it writable, or use another file with --config.",
+            config_path.display()
+        ));
+    }
+    let config_dir = match config_path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    if !config_dir.is_dir() {
+        return Err(format!("'{}' doesn't exist and couldn't be created, so your changes can't be saved.", config_dir.display()));
+    }
+    let probe_path = with_suffix(config_path, ".tmp");
+    match File::create(&probe_path) {
+        Ok(_) => {
+            let _ = fs::remove_file(&probe_path);
+            Ok(())
         }
-        Commands::List => {
-            if config.bookmarks.is_empty() {
-                println!("You have no bookmarks.");
-            } else {
-                println!("Your bookmarks:");
-                for (index, bookmark) in config.bookmarks.iter().enumerate() {
-                    println!("{}. Alias: '{}', Path: '{}'", index + 1, bookmark.alias, bookmark.path.display());
-                }
-            }
+        Err(err) => Err(format!(
+            "Can't save your changes, because '{}' isn't writable: {}. Check its permissions, and whether it's on a read-only mount.",
+            config_dir.display(),
+            err
+        )),
+    }
+}
+
+// Returns the path with a suffix appended to its file name, e.g. 'config.toml' becomes 'config.toml.bak'.
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(suffix);
+    PathBuf::from(path)
+}
+
+// Returns the path of the single rolling backup of the config, 'config.toml.bak' next to the config file.
+fn get_backup_path(config_path: &Path) -> PathBuf {
+    with_suffix(config_path, ".bak")
+}
+
+// Returns the path of the undo snapshot, 'config.toml.prev' next to the config file.
+// It holds the config as it was before the last change made through save_config.
+fn get_undo_path(config_path: &Path) -> PathBuf {
+    with_suffix(config_path, ".prev")
+}
+
+// Returns the path of the file remembering the directory the last jump started from, 'config.toml.last_dir' next
+// to the config file. `jump --back` goes there.
+fn get_last_dir_path(config_path: &Path) -> PathBuf {
+    with_suffix(config_path, ".last_dir")
+}
+
+// Takes an exclusive advisory lock on 'config.toml.lock' next to the config file, so concurrent pomelo processes
+// don't overwrite each other's changes. If another process holds the lock, this says so on stderr and waits for it.
+// The lock lasts as long as the returned file stays open, and the OS drops it when the process exits, even when
+// that happens through `fail`.
+// In a directory that can't be written, there is no lock, and nothing to guard either: reading commands work
+// without it, and changes fail in check_writable.
+fn lock_config(config_path: &Path) -> Result<Option<File>, String> {
+    let lock_path = with_suffix(config_path, ".lock");
+    let config_dir = config_path.parent().expect("Failed to get config directory path");
+    if !config_dir.as_os_str().is_empty() && !config_dir.exists() {
+        match create_config_dir(config_dir) {
+            Ok(()) => {}
+            Err(err) if is_read_only_error(&err) => return Ok(None),
+            Err(err) => return Err(format!("Failed to create '{}': {}", config_dir.display(), err)),
         }
-        Commands::Jump { alias } => {
-            // :)
+    }
+    let file = match File::create(&lock_path) {
+        Ok(file) => file,
+        Err(err) if is_read_only_error(&err) => return Ok(None),
+        Err(err) => return Err(format!("Failed to create lock file '{}': {}", lock_path.display(), err)),
+    };
+    match file.try_lock() {
+        Ok(()) => return Ok(Some(file)),
+        Err(fs::TryLockError::WouldBlock) => eprintln!("Waiting for another pomelo process to finish..."),
+        Err(fs::TryLockError::Error(err)) => return Err(format!("Failed to lock '{}': {}", lock_path.display(), err)),
+    }
+    file.lock().map_err(|err| format!("Failed to lock '{}': {}", lock_path.display(), err))?;
+    Ok(Some(file))
+}
+
+// Copies the current config file to the backup path before a destructive change, replacing any older backup.
+// Does nothing if there is no config file yet, or with --dry-run or --no-save.
+fn backup_config(config_path: &Path, options: SaveOptions) {
+    if config_path.exists() && !options.discards_changes() {
+        if let Err(err) = check_writable(config_path) {
+            fail(options.format, err);
         }
+        fs::copy(config_path, get_backup_path(config_path)).expect("Failed to back up config file");
+    }
+}
+
+// Constructs and returns the path to the configuration file.
+// A path passed with --config wins, followed by the file of the profile passed with --profile (see profile_path).
+// Otherwise, if the POMELO_CONFIG environment variable is set (and not empty), its value is used verbatim.
+// Otherwise it falls back to default_config_path, which is what the default profile uses.
+// The result is resolved once in main and passed to everything that reads or writes the config.
+// Returns an error if the default location is needed but can't be determined (see default_config_path), and if
+// the path is an existing directory, which is an easy mistake to make with POMELO_CONFIG or --config.
+fn get_config_path(cli_path: Option<&Path>, profile: Option<&str>) -> Result<PathBuf, String> {
+    let (path, source) = if let Some(path) = cli_path {
+        (path.to_path_buf(), "--config")
+    } else if let Some(profile) = profile {
+        (profile_path(profile)?, "--profile")
+    } else if let Some(path) = env::var_os("POMELO_CONFIG").filter(|path| !path.is_empty()) {
+        (PathBuf::from(path), "POMELO_CONFIG")
+    } else {
+        (default_config_path()?, "the default location")
+    };
+
+    if path.is_dir() {
+        return Err(format!(
+            "The config path '{}' from {} is a directory, but it has to be a file, e.g. '{}'",
+            path.display(),
+            source,
+            path.join("config.toml").display()
+        ));
+    }
+    Ok(path)
+}
+
+// Returns the platform's default location for the configuration file.
+// It determines the config directory using the dirs crate ($XDG_CONFIG_HOME or '~/.config' on Linux,
+// '~/Library/Application Support' on macOS, '%APPDATA%' on Windows) and appends 'pomelo/config.toml', or the JSON
+// or YAML config there if that's what exists (see find_config_file).
+// In minimal environments like containers or cron jobs, HOME may be unset and there is no such directory, in
+// which case this returns an error explaining how to point pomelo at a config file instead.
+fn default_config_path() -> Result<PathBuf, String> {
+    let config_dir = dirs::config_dir().ok_or_else(|| {
+        "Couldn't find your config directory. Set HOME, or choose a config file with POMELO_CONFIG or --config.".to_string()
+    })?;
+    Ok(find_config_file(&config_dir.join("pomelo"), "config"))
+}
+
+// Returns the config file named `stem` in the directory, trying '.toml', '.json', '.yaml' and '.yml' in turn.
+// When none of them exists yet, the TOML one is returned, so new configs are TOML.
+fn find_config_file(dir: &Path, stem: &str) -> PathBuf {
+    ["toml", "json", "yaml", "yml"]
+        .iter()
+        .map(|extension| dir.join(format!("{}.{}", stem, extension)))
+        .find(|path| path.exists())
+        .unwrap_or_else(|| dir.join(format!("{}.toml", stem)))
+}
+
+// Returns the directory holding the profile files, 'profiles' next to default_config_path.
+fn profiles_dir() -> Result<PathBuf, String> {
+    default_config_path().map(|path| path.with_file_name("profiles"))
+}
+
+// Returns the path of the config file for a named profile, '<NAME>.toml' (or '.json', '.yaml') in profiles_dir.
+// The file is created on the first save, just like the default config.
+fn profile_path(name: &str) -> Result<PathBuf, String> {
+    profiles_dir().map(|dir| find_config_file(&dir, name))
+}
+
+// Checks that a profile name can be used as a file name: it must not be empty, must only contain ASCII letters,
+// digits, '_' and '-', and 'default' is reserved for the default config.
+fn validate_profile(name: &str) -> Result<(), String> {
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+        return Err(format!("Invalid profile '{}': profile names may only contain letters, digits, '_' and '-'", name));
+    }
+    if name == "default" {
+        return Err("'default' is the default profile. Leave out --profile to use it.".to_string());
+    }
+    Ok(())
+}
+
+// Moves the configuration file from '~/.pomelo/config.toml', where older versions stored it, to default_config_path.
+// This only happens when neither --config nor POMELO_CONFIG is used, the old file exists and nothing exists at the
+// new location yet, so an existing config is never overwritten. The migration is logged to stderr so stdout stays clean for `jump`.
+fn migrate_legacy_config(config_path: &Path) {
+    if default_config_path().ok().as_deref() != Some(config_path) || config_path.exists() {
+        return;
+    }
+    let Some(legacy_dir) = dirs::home_dir().map(|home| home.join(".pomelo")) else {
+        return;
+    };
+    let legacy_path = legacy_dir.join("config.toml");
+    if !legacy_path.is_file() {
+        return;
+    }
+
+    let config_dir = config_path.parent().expect("Failed to get config directory path");
+    if !config_dir.exists() {
+        create_config_dir(config_dir).expect("Failed to create config directory");
+    }
+    // A rename fails across filesystems, so fall back to copying and removing the old file.
+    if fs::rename(&legacy_path, config_path).is_err() {
+        fs::copy(&legacy_path, config_path).expect("Failed to migrate config file");
+        fs::remove_file(&legacy_path).expect("Failed to remove old config file");
+    }
+    // Only succeeds if nothing else was left in the old directory.
+    let _ = fs::remove_dir(&legacy_dir);
+
+    eprintln!("Migrated config from '{}' to '{}'", legacy_path.display(), config_path.display());
+}
+
+// Reports the outcome of a command, as the human-readable message or as the JSON object depending on the format.
+// With --quiet the message is left out, but the JSON object is still printed since scripts rely on it.
+fn report(format: OutputFormat, verbosity: Verbosity, message: &str, json: serde_json::Value) {
+    match format {
+        OutputFormat::Text if verbosity == Verbosity::Quiet => {}
+        OutputFormat::Text => println!("{}", message),
+        OutputFormat::Json => println!("{}", json),
+    }
+}
+
+// Prints a message about what a command did, unless --quiet was passed.
+fn say(verbosity: Verbosity, message: impl fmt::Display) {
+    if verbosity > Verbosity::Quiet {
+        println!("{}", message);
+    }
+}
+
+// Prints a detail only wanted with --verbose. It goes to stderr so stdout stays clean for `jump`.
+fn trace(verbosity: Verbosity, message: impl fmt::Display) {
+    if verbosity == Verbosity::Verbose {
+        eprintln!("{}", message);
+    }
+}
+
+// Reports an error, for commands that carry on after one and exit with a non-zero status at the end.
+// In text mode the message goes to stderr. In JSON mode it is printed to stdout as {"error": "..."}, like any
+// other result, so scripts only have to read one stream.
+fn report_error(format: OutputFormat, message: impl fmt::Display) {
+    match format {
+        OutputFormat::Text => eprintln!("{}", message),
+        OutputFormat::Json => println!("{}", json!({ "error": message.to_string() })),
+    }
+}
+
+// Reports an error (see report_error) and exits with a non-zero status.
+fn fail(format: OutputFormat, message: impl fmt::Display) -> ! {
+    report_error(format, message);
+    process::exit(1);
+}
+
+// The outcome of one of the checks run by `doctor`. Only Fail makes `doctor` exit with a non-zero status.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum CheckStatus {
+    Pass,
+    Warn,
+    Fail,
+}
+
+impl CheckStatus {
+    fn marker(self) -> &'static str {
+        match self {
+            CheckStatus::Pass => "[ok]  ",
+            CheckStatus::Warn => "[warn]",
+            CheckStatus::Fail => "[fail]",
+        }
+    }
+
+    fn style(self) -> Style {
+        match self {
+            CheckStatus::Pass => Style::Success,
+            CheckStatus::Warn => Style::Warning,
+            CheckStatus::Fail => Style::Error,
+        }
+    }
+}
+
+// Runs every check of `doctor` against the config at the given path and returns their results in order.
+// This reads the file itself rather than taking a loaded Config, so it can report a config that fails to load.
+fn diagnose(config_path: &Path) -> Vec<(CheckStatus, String)> {
+    let mut checks = Vec::new();
+    let config = match read_config(config_path) {
+        Ok(config) => {
+            checks.push((CheckStatus::Pass, format!("Config file '{}' is valid", config_path.display())));
+            config
+        }
+        Err(ConfigError::Read { source, .. }) if source.kind() == io::ErrorKind::NotFound => {
+            checks.push((CheckStatus::Pass, format!("No config file at '{}' yet, it is created on the first save", config_path.display())));
+            return checks;
+        }
+        Err(err) => {
+            checks.push((CheckStatus::Fail, err.to_string()));
+            return checks;
+        }
+    };
+
+    let invalid: Vec<&str> = config.bookmarks
+        .iter()
+        .filter(|bookmark| validate_alias(&bookmark.alias).is_err())
+        .map(|bookmark| bookmark.alias.as_str())
+        .collect();
+    checks.push(match invalid.as_slice() {
+        [] => (CheckStatus::Pass, "All aliases are valid".to_string()),
+        invalid => (CheckStatus::Warn, format!("Aliases that can't be typed or completed reliably: {}", invalid.join(", "))),
+    });
+
+    let missing: Vec<String> = config.bookmarks
+        .iter()
+        .filter(|bookmark| bookmark.is_missing())
+        .map(|bookmark| format!("{} ('{}')", bookmark.alias, bookmark.path.display()))
+        .collect();
+    checks.push(match missing.as_slice() {
+        [] => (CheckStatus::Pass, "All bookmarked paths exist".to_string()),
+        missing => (CheckStatus::Warn, format!("{} bookmark(s) point at a missing path: {}", missing.len(), missing.join(", "))),
+    });
+
+    let wrong_kind: Vec<String> = config.bookmarks
+        .iter()
+        .filter(|bookmark| bookmark.target().is_ok_and(|path| path.exists() && bookmark.check_kind(&path).is_err()))
+        .map(|bookmark| format!("{} ('{}')", bookmark.alias, bookmark.path.display()))
+        .collect();
+    checks.push(match wrong_kind.as_slice() {
+        [] => (CheckStatus::Pass, "All bookmarked paths are directories, or files for file bookmarks".to_string()),
+        wrong => (CheckStatus::Warn, format!("{} bookmark(s) point at a file where a directory was bookmarked, or the other way around: {}", wrong.len(), wrong.join(", "))),
+    });
+
+    // Only the first of several bookmarks with the same alias can ever be reached.
+    let mut seen = BTreeSet::new();
+    let duplicate_aliases: BTreeSet<&str> = config.bookmarks
+        .iter()
+        .map(|bookmark| bookmark.alias.as_str())
+        .filter(|alias| !seen.insert(*alias))
+        .collect();
+    checks.push(if duplicate_aliases.is_empty() {
+        (CheckStatus::Pass, "No alias is used more than once".to_string())
+    } else {
+        let aliases: Vec<&str> = duplicate_aliases.into_iter().collect();
+        (CheckStatus::Fail, format!("Aliases used by more than one bookmark: {}", aliases.join(", ")))
+    });
+
+    // Bookmarking a directory twice can be intentional, so this only warns.
+    let duplicate_paths = find_duplicate_paths(&config.bookmarks);
+    checks.push(if duplicate_paths.is_empty() {
+        (CheckStatus::Pass, "No directory is bookmarked more than once".to_string())
+    } else {
+        let paths: Vec<String> = duplicate_paths
+            .iter()
+            .map(|(path, aliases)| format!("'{}' as {}", path.display(), aliases.join(", ")))
+            .collect();
+        (CheckStatus::Warn, format!("Directories bookmarked more than once: {}", paths.join("; ")))
+    });
+
+    // The config file is written with mode 0600, so anything looser was changed by hand or another tool.
+    #[cfg(unix)]
+    if let Ok(metadata) = fs::metadata(config_path) {
+        let mode = metadata.permissions().mode() & 0o777;
+        checks.push(if mode & 0o077 == 0 {
+            (CheckStatus::Pass, "Only you can read the config file".to_string())
+        } else {
+            (CheckStatus::Warn, format!("Other users can read the config file, its mode is {:o} instead of 600", mode))
+        });
+    }
+
+    checks
+}
+
+// Groups the bookmarks by canonical path and returns the paths bookmarked under more than one alias,
+// along with those aliases.
+fn find_duplicate_paths(bookmarks: &[Bookmark]) -> Vec<(PathBuf, Vec<&str>)> {
+    let mut aliases_by_path: BTreeMap<PathBuf, Vec<&str>> = BTreeMap::new();
+    for bookmark in bookmarks {
+        aliases_by_path.entry(canonical_path(&bookmark.path)).or_default().push(&bookmark.alias);
+    }
+    aliases_by_path.into_iter().filter(|(_, aliases)| aliases.len() > 1).collect()
+}
+
+// Removes all but one bookmark of every group sharing the same alias or canonical path. The one kept is the pinned
+// one, or else the most visited one, or else the one added first, and it stays where it was in the list.
+// Returns the removed bookmarks, each with the alias of the bookmark kept in its place.
+fn dedup_bookmarks(bookmarks: &mut Vec<Bookmark>, by: DedupKey) -> Vec<(Bookmark, String)> {
+    let mut groups: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+    for (index, bookmark) in bookmarks.iter().enumerate() {
+        let key = match by {
+            DedupKey::Alias => bookmark.alias.clone(),
+            DedupKey::Path => canonical_path(&bookmark.path).to_string_lossy().into_owned(),
+        };
+        groups.entry(key).or_default().push(index);
+    }
+
+    let mut kept_for = vec![None; bookmarks.len()];
+    for indices in groups.values().filter(|indices| indices.len() > 1) {
+        let keeper = *indices
+            .iter()
+            .max_by_key(|&&index| (bookmarks[index].pinned, bookmarks[index].visits, std::cmp::Reverse(index)))
+            .expect("Groups aren't empty");
+        for &index in indices.iter().filter(|&&index| index != keeper) {
+            kept_for[index] = Some(bookmarks[keeper].alias.clone());
+        }
+    }
+
+    let mut removed = Vec::new();
+    let mut kept = Vec::new();
+    for (bookmark, kept_instead) in bookmarks.drain(..).zip(kept_for) {
+        match kept_instead {
+            Some(alias) => removed.push((bookmark, alias)),
+            None => kept.push(bookmark),
+        }
+    }
+    *bookmarks = kept;
+    removed
+}
+
+// Returns where zoxide or autojump keep their database by default, or None if that can't be determined.
+// zoxide honours $_ZO_DATA_DIR, and otherwise uses the platform's local data directory.
+fn default_database_path(source: ImportSource) -> Option<PathBuf> {
+    match source {
+        ImportSource::Pomelo => None,
+        ImportSource::Zoxide => match env::var_os("_ZO_DATA_DIR").filter(|dir| !dir.is_empty()) {
+            Some(dir) => Some(PathBuf::from(dir).join("db.zo")),
+            None => dirs::data_local_dir().map(|dir| dir.join("zoxide").join("db.zo")),
+        },
+        #[cfg(target_os = "macos")]
+        ImportSource::Autojump => dirs::home_dir().map(|home| home.join("Library").join("autojump").join("autojump.txt")),
+        #[cfg(not(target_os = "macos"))]
+        ImportSource::Autojump => dirs::data_dir().map(|dir| dir.join("autojump").join("autojump.txt")),
+    }
+}
+
+// Reads the directories and their scores from a zoxide or autojump database.
+// autojump stores one '<weight>\t<path>' line per directory.
+// zoxide stores a little-endian u32 format version (3), followed by its directories serialized with bincode:
+// a u64 count, then for each directory the path as a u64 length and UTF-8 bytes, the rank as an f64 and the
+// time of the last access as a u64.
+fn read_database(source: ImportSource, path: &Path) -> Result<Vec<(PathBuf, f64)>, String> {
+    let read_error = |err: io::Error| format!("Failed to read '{}': {}", path.display(), err);
+    let invalid = || format!("'{}' is not a valid {:?} database", path.display(), source);
+    match source {
+        ImportSource::Pomelo => unreachable!("pomelo exports are read with read_config"),
+        ImportSource::Autojump => {
+            let contents = fs::read_to_string(path).map_err(read_error)?;
+            contents
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(|line| {
+                    let (weight, dir) = line.split_once('\t').ok_or_else(invalid)?;
+                    let weight: f64 = weight.trim().parse().map_err(|_| invalid())?;
+                    Ok((PathBuf::from(dir), weight))
+                })
+                .collect()
+        }
+        ImportSource::Zoxide => {
+            let bytes = fs::read(path).map_err(read_error)?;
+            let mut rest = bytes.as_slice();
+            let mut take = |len: usize| -> Result<&[u8], String> {
+                if rest.len() < len {
+                    return Err(invalid());
+                }
+                let (taken, remaining) = rest.split_at(len);
+                rest = remaining;
+                Ok(taken)
+            };
+
+            let version = u32::from_le_bytes(take(4)?.try_into().expect("took 4 bytes"));
+            if version != 3 {
+                return Err(format!("'{}' uses zoxide database version {}, only version 3 is supported", path.display(), version));
+            }
+            let count = u64::from_le_bytes(take(8)?.try_into().expect("took 8 bytes"));
+            let mut entries = Vec::new();
+            for _ in 0..count {
+                let len = u64::from_le_bytes(take(8)?.try_into().expect("took 8 bytes"));
+                let len = usize::try_from(len).map_err(|_| invalid())?;
+                let dir = String::from_utf8(take(len)?.to_vec()).map_err(|_| invalid())?;
+                let rank = f64::from_le_bytes(take(8)?.try_into().expect("took 8 bytes"));
+                let _last_accessed = take(8)?;
+                entries.push((PathBuf::from(dir), rank));
+            }
+            Ok(entries)
+        }
+    }
+}
+
+// Turns the directories read from a zoxide or autojump database into bookmarks named after the directory.
+// Directories are taken in order of their score, so when two share a name the more used one gets the alias,
+// and the score, rounded, becomes the visit count (see alias_from_name).
+// Returns the bookmarks along with the number of directories skipped because they no longer exist.
+fn bookmarks_from_database(mut entries: Vec<(PathBuf, f64)>) -> (Vec<Bookmark>, usize) {
+    entries.sort_by(|a, b| b.1.total_cmp(&a.1));
+    let mut bookmarks: Vec<Bookmark> = Vec::new();
+    let mut missing = 0;
+    for (path, score) in entries {
+        if !path.is_dir() {
+            missing += 1;
+            continue;
+        }
+        let Some(name) = path.file_name() else {
+            continue;
+        };
+        let alias = alias_from_name(name);
+        if alias.is_empty() || bookmarks.iter().any(|b| b.alias == alias) {
+            continue;
+        }
+        let mut bookmark = Bookmark::new(alias, path);
+        bookmark.visits = score.max(0.0).round() as u64;
+        bookmarks.push(bookmark);
+    }
+    (bookmarks, missing)
+}
+
+// Turns a file or directory name into an alias, replacing characters aliases can't contain with '-' and trimming
+// them off the ends. Returns an empty string for a name without anything usable.
+fn alias_from_name(name: &OsStr) -> String {
+    let alias: String = name
+        .to_string_lossy()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '_' || c == '-' { c } else { '-' })
+        .collect();
+    alias.trim_matches('-').to_string()
+}
+
+// Checks that an alias is usable from the shell and in completions: it must not be empty and may only contain
+// ASCII letters, digits, '_' and '-', and '/' to separate namespaces like `work/api` (see `list --group`).
+// Returns an error message naming the alias otherwise.
+fn validate_alias(alias: &str) -> Result<(), String> {
+    if alias.is_empty() {
+        return Err("Alias must not be empty".to_string());
+    }
+    if !alias.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '/') {
+        return Err(format!("Invalid alias '{}': aliases may only contain letters, digits, '_', '-' and '/'", alias));
+    }
+    if alias.split('/').any(str::is_empty) {
+        return Err(format!("Invalid alias '{}': '/' has to separate two non-empty parts, as in 'work/api'", alias));
+    }
+    Ok(())
+}
+
+// Whether the alias is in the namespace `group`, like `work/api` is in `work`. Nested namespaces count too, so
+// `work/api/v2` is in both `work` and `work/api`.
+fn in_group(alias: &str, group: &str) -> bool {
+    let group = group.trim_end_matches('/');
+    alias.strip_prefix(group).is_some_and(|rest| rest.starts_with('/'))
+}
+
+// Returns the completions of `prefix` among the aliases, one namespace at a time: aliases in a namespace beyond the
+// prefix are offered as that namespace, ending in '/'. With `work/api` and `work/web`, `w` completes to `work/`,
+// and `work/` to both aliases.
+fn complete_alias(aliases: &[&str], prefix: &str) -> BTreeSet<String> {
+    aliases
+        .iter()
+        .filter_map(|alias| alias.strip_prefix(prefix).map(|rest| (alias, rest)))
+        .map(|(alias, rest)| match rest.find('/') {
+            Some(end) => alias[..prefix.len() + end + 1].to_string(),
+            None => alias.to_string(),
+        })
+        .collect()
+}
+
+// Expands a leading '~' to the home directory and substitutes $VAR and ${VAR} with the values of environment variables.
+// Paths without either are returned untouched. A '$' that isn't followed by a variable name is kept as is.
+// Returns an error naming the variable when a referenced environment variable isn't set.
+fn expand_path(path: &Path) -> Result<PathBuf, String> {
+    let Some(path) = path.to_str() else {
+        return Ok(path.to_path_buf());
+    };
+
+    let mut expanded = String::new();
+    let mut rest = path;
+    if rest == "~" || rest.strip_prefix('~').is_some_and(|after| after.starts_with(std::path::is_separator)) {
+        let home_dir = dirs::home_dir().ok_or_else(|| "Failed to find home directory".to_string())?;
+        expanded.push_str(&home_dir.to_string_lossy());
+        rest = &rest[1..];
+    }
+
+    while let Some(start) = rest.find('$') {
+        expanded.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let (name, remaining) = match after.strip_prefix('{') {
+            Some(braced) => {
+                let end = braced.find('}').ok_or_else(|| format!("Unclosed '${{' in '{}'", path))?;
+                (&braced[..end], &braced[end + 1..])
+            }
+            None => {
+                let end = after
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                    .unwrap_or(after.len());
+                (&after[..end], &after[end..])
+            }
+        };
+        if name.is_empty() {
+            expanded.push('$');
+            rest = after;
+            continue;
+        }
+
+        let value = env::var(name)
+            .map_err(|_| format!("Environment variable '{}' used in '{}' is not set", name, path))?;
+        expanded.push_str(&value);
+        rest = remaining;
+    }
+    expanded.push_str(rest);
+
+    Ok(PathBuf::from(expanded))
+}
+
+// Checks that a path given for a bookmark is an existing directory, or file for a file bookmark, and turns it into
+// an absolute path. By default existing paths are canonicalized, so relative inputs like '../foo' are stored as absolute paths
+// and symlinks are resolved to the real location. Without `canonicalize`, the path is only made absolute, which
+// keeps a symlink as it was given.
+// With `allow_missing`, a path that doesn't exist is accepted and only made absolute, since it can't be canonicalized.
+// Returns an error message naming the offending path otherwise.
+fn resolve_bookmark_path(path: &Path, kind: BookmarkKind, allow_missing: bool, canonicalize: bool) -> Result<PathBuf, String> {
+    let matches_kind = match kind {
+        BookmarkKind::Dir => path.is_dir(),
+        BookmarkKind::File => path.is_file(),
+    };
+    if matches_kind && canonicalize {
+        fs::canonicalize(path).map_err(|err| format!("Failed to resolve '{}': {}", path.display(), err))
+    } else if matches_kind {
+        std::path::absolute(path).map_err(|err| format!("Failed to resolve '{}': {}", path.display(), err))
+    } else if path.exists() && kind == BookmarkKind::File {
+        Err(format!("'{}' is not a file", path.display()))
+    } else if path.exists() {
+        Err(format!("'{}' is not a directory", path.display()))
+    } else if allow_missing {
+        std::path::absolute(path).map_err(|err| format!("Failed to resolve '{}': {}", path.display(), err))
+    } else {
+        Err(format!("'{}' does not exist. Use --allow-missing to bookmark it anyway.", path.display()))
+    }
+}
+
+// Returns the canonical form of a bookmarked path, so that different spellings of the same directory compare equal.
+// Paths that can't be canonicalized (e.g. because the directory is missing) are returned as stored.
+fn canonical_path(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+// Finds the bookmark with the given alias and returns its index.
+// With `ignore_case`, aliases are compared ignoring ASCII case, but an alias that matches exactly still wins.
+// On failure it returns the bookmarks that matched, which is empty when nothing matched at all and has several
+// entries when the alias matches bookmarks that differ only in case.
+fn find_bookmark<'a>(bookmarks: &'a [Bookmark], alias: &str, ignore_case: bool) -> Result<usize, Vec<&'a Bookmark>> {
+    if let Some(index) = bookmarks.iter().position(|b| b.alias == alias) {
+        return Ok(index);
+    }
+    if !ignore_case {
+        return Err(Vec::new());
+    }
+
+    let matches: Vec<usize> = (0..bookmarks.len())
+        .filter(|&index| bookmarks[index].alias.eq_ignore_ascii_case(alias))
+        .collect();
+    match matches.as_slice() {
+        [index] => Ok(*index),
+        _ => Err(matches.into_iter().map(|index| &bookmarks[index]).collect()),
+    }
+}
+
+// Finds the bookmark for `jump --ancestor`: the one whose directory contains `dir` most closely.
+// Both sides are compared canonicalized. A bookmark of `dir` itself doesn't count, as jumping there would go nowhere.
+fn find_ancestor_bookmark(bookmarks: &[Bookmark], dir: &Path) -> Option<usize> {
+    let dir = canonical_path(dir);
+    bookmarks
+        .iter()
+        .enumerate()
+        .filter(|(_, bookmark)| bookmark.kind == BookmarkKind::Dir)
+        .filter_map(|(index, bookmark)| Some((index, canonical_path(&bookmark.target().ok()?))))
+        .filter(|(_, path)| dir.starts_with(path) && *path != dir)
+        .max_by_key(|(_, path)| path.components().count())
+        .map(|(index, _)| index)
+}
+
+// How far the best partial match of `jump` has to score above the next one to be picked (see find_jump_target).
+const MIN_SCORE_LEAD: f64 = 0.25;
+
+// Finds the bookmark `jump` should go to.
+// An alias match (see find_bookmark) always wins. Otherwise, unless `exact` is set, every bookmark whose alias
+// contains the query (ignoring case) is a candidate. A single candidate is the target, and among several, the one
+// with the highest score, as long as it leads every other candidate by at least MIN_SCORE_LEAD:
+//
+//     score = match_weight * match + frecency_weight * frecency + pinned
+//
+// where `match` is how much of the alias the query covers (1 when it's the whole alias), plus 1 when the alias
+// starts with the query, halved so it's between 0 and 1, `frecency` is the candidate's frecency (see frecency)
+// divided by the highest one among the candidates, also between 0 and 1, and `pinned` is 1 for a pinned bookmark.
+// A small lead, like one alias being a little shorter than another, isn't a reason to pick one over the other,
+// so the query is ambiguous then. Setting both weights to 0 leaves only pinning to tell candidates apart.
+// On success it returns the index of the bookmark. On failure it returns the candidates that matched, pinned ones
+// first and then by score, which is empty when nothing matched at all.
+fn find_jump_target<'a>(bookmarks: &'a [Bookmark], query: &str, exact: bool, ignore_case: bool, settings: &Settings) -> Result<usize, Vec<&'a Bookmark>> {
+    match find_bookmark(bookmarks, query, ignore_case) {
+        Ok(index) => return Ok(index),
+        Err(candidates) if !candidates.is_empty() || exact => return Err(candidates),
+        Err(_) => {}
+    }
+
+    let query = query.to_lowercase();
+    let candidates: Vec<usize> = (0..bookmarks.len())
+        .filter(|&index| bookmarks[index].alias.to_lowercase().contains(&query))
+        .collect();
+    if let [index] = candidates.as_slice() {
+        return Ok(*index);
+    }
+
+    let now = Utc::now();
+    let best_frecency = candidates.iter().map(|&index| frecency(&bookmarks[index], now)).fold(0.0, f64::max);
+    let score = |index: usize| {
+        let alias = bookmarks[index].alias.to_lowercase();
+        let coverage = query.chars().count() as f64 / alias.chars().count() as f64;
+        let prefix = if alias.starts_with(&query) { 1.0 } else { 0.0 };
+        let frecency = if best_frecency > 0.0 { frecency(&bookmarks[index], now) / best_frecency } else { 0.0 };
+        let pinned = if bookmarks[index].pinned { 1.0 } else { 0.0 };
+        settings.match_weight * (coverage + prefix) / 2.0 + settings.frecency_weight * frecency + pinned
+    };
+    let mut scored: Vec<(usize, f64)> = candidates.into_iter().map(|index| (index, score(index))).collect();
+    scored.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+    if let [(index, best), (_, second), ..] = scored.as_slice() {
+        if best - second >= MIN_SCORE_LEAD {
+            return Ok(*index);
+        }
+    }
+
+    // Sorting is stable, so candidates that aren't pinned stay in score order.
+    scored.sort_by(|&(a, _), &(b, _)| bookmarks[b].pinned.cmp(&bookmarks[a].pinned));
+    Err(scored.into_iter().map(|(index, _)| &bookmarks[index]).collect())
+}
+
+// Runs git with the given arguments in a directory and returns what it printed to stdout.
+// When git fails, or can't be started, the error message includes what git printed to stderr.
+fn run_git(dir: &Path, args: &[&str]) -> Result<String, String> {
+    let output = process::Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .map_err(|err| format!("Failed to run git: {}", err))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("`git {}` failed: {}", args.join(" "), stderr.trim()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+// Opens the file in the user's editor and waits for it to exit. $VISUAL is preferred over $EDITOR, falling back to
+// DEFAULT_EDITOR. The variable may include arguments, as in `code --wait`.
+fn run_editor(path: &Path) -> Result<(), String> {
+    let editor = env::var("VISUAL")
+        .ok()
+        .filter(|editor| !editor.trim().is_empty())
+        .or_else(|| env::var("EDITOR").ok().filter(|editor| !editor.trim().is_empty()))
+        .unwrap_or_else(|| DEFAULT_EDITOR.to_string());
+    let mut words = editor.split_whitespace();
+    let program = words.next().expect("The editor isn't empty");
+    let status = process::Command::new(program)
+        .args(words)
+        .arg(path)
+        .status()
+        .map_err(|err| format!("Failed to run '{}': {}", editor, err))?;
+    if !status.success() {
+        return Err(format!("'{}' exited with {}", editor, status));
+    }
+    Ok(())
+}
+
+// Returns the path as it should be printed, quoted for a POSIX shell with `shell_escape` (see shell_quote).
+fn display_path(path: &Path, shell_escape: bool) -> String {
+    let path = path.display().to_string();
+    if shell_escape {
+        shell_quote(&path)
+    } else {
+        path
+    }
+}
+
+// Quotes text so a POSIX shell reads it back as a single word, unchanged.
+// Text made only of characters no shell treats specially is returned as is. Anything else is wrapped in single
+// quotes, within which only the single quote itself needs escaping, as '\''.
+fn shell_quote(text: &str) -> String {
+    let safe = |c: char| c.is_ascii_alphanumeric() || "/._-+:@%,=".contains(c);
+    if !text.is_empty() && text.chars().all(safe) {
+        return text.to_string();
+    }
+    format!("'{}'", text.replace('\'', "'\\''"))
+}
+
+// Wraps text in the ANSI escape codes for the style, or returns it unchanged when color is off.
+fn paint(text: &str, style: Style, color: bool) -> String {
+    if color {
+        format!("\x1b[{}m{}\x1b[0m", style.code(), text)
+    } else {
+        text.to_string()
+    }
+}
+
+// Decides whether text output should be colored: not with --no-color, not when NO_COLOR is set to anything
+// (see https://no-color.org), and only when stdout is a terminal.
+fn use_color(no_color: bool) -> bool {
+    let no_color_env = env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+    !no_color && !no_color_env && io::stdout().is_terminal()
+}
+
+// A piece of a `list --format-string` template.
+#[derive(Debug)]
+enum TemplatePart {
+    Literal(String),
+    Placeholder(String),
+}
+
+// The placeholders a `list --format-string` template may use.
+const PLACEHOLDERS: &[&str] = &["index", "alias", "path", "name", "visits", "last_visited", "created_at", "tags", "description"];
+
+// Splits a `list --format-string` template into literal text and placeholders, turning `{{` and `}}` into literal
+// braces. Returns an error for unknown placeholders and unbalanced braces, so a typo fails before anything is listed.
+fn parse_template(template: &str) -> Result<Vec<TemplatePart>, String> {
+    let mut parts = Vec::new();
+    let mut literal = String::new();
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                literal.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                literal.push('}');
+            }
+            '{' => {
+                let mut name = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => name.push(c),
+                        None => return Err(format!("Unclosed '{{' in '{}'. Write '{{{{' for a literal brace", template)),
+                    }
+                }
+                if !PLACEHOLDERS.contains(&name.as_str()) {
+                    return Err(format!(
+                        "Unknown placeholder '{{{}}}' in '{}'. Use one of: {{{}}}",
+                        name,
+                        template,
+                        PLACEHOLDERS.join("}, {")
+                    ));
+                }
+                parts.push(TemplatePart::Literal(std::mem::take(&mut literal)));
+                parts.push(TemplatePart::Placeholder(name));
+            }
+            '}' => return Err(format!("Unmatched '}}' in '{}'. Write '}}}}' for a literal brace", template)),
+            c => literal.push(c),
+        }
+    }
+    parts.push(TemplatePart::Literal(literal));
+    Ok(parts)
+}
+
+// Fills in a parsed `list --format-string` template for one bookmark. Values that aren't set, like the
+// last visit of a bookmark that was never jumped to, are left empty.
+fn render_template(parts: &[TemplatePart], index: usize, bookmark: &Bookmark, relative: bool) -> String {
+    let time = |time: Option<DateTime<Utc>>| time.map(|time| time.to_rfc3339_opts(SecondsFormat::Secs, true)).unwrap_or_default();
+    let mut output = String::new();
+    for part in parts {
+        match part {
+            TemplatePart::Literal(text) => output.push_str(text),
+            TemplatePart::Placeholder(name) => output.push_str(&match name.as_str() {
+                "index" => (index + 1).to_string(),
+                "alias" => bookmark.alias.clone(),
+                "path" if relative => tilde_path(&bookmark.path),
+                "path" => bookmark.path.display().to_string(),
+                "name" => dir_name(&bookmark.path),
+                "visits" => bookmark.visits.to_string(),
+                "last_visited" => time(bookmark.last_visited),
+                "created_at" => time(bookmark.created_at),
+                "tags" => bookmark.tags.join(","),
+                "description" => bookmark.description.clone().unwrap_or_default(),
+                _ => unreachable!("parse_template only accepts known placeholders"),
+            }),
+        }
+    }
+    output
+}
+
+// How print_bookmark shows a bookmark.
+#[derive(Clone, Copy, Debug, Default)]
+struct ListStyle {
+    // Adds indented lines with the creation time, tags, description, on-jump command and alternate paths.
+    long: bool,
+    // Highlights the output with ANSI colors (see use_color).
+    color: bool,
+    // Abbreviates paths under the home directory with '~' (see tilde_path).
+    relative: bool,
+}
+
+// Puts the text on the clipboard with the first of CLIPBOARD_COMMANDS that is installed.
+// Returns an error if none of them is, or if the one found fails.
+fn copy_to_clipboard(text: &str) -> Result<(), String> {
+    for (program, args) in CLIPBOARD_COMMANDS {
+        let mut child = match process::Command::new(program).args(*args).stdin(process::Stdio::piped()).spawn() {
+            Ok(child) => child,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+            Err(err) => return Err(format!("Failed to run '{}': {}", program, err)),
+        };
+        let mut stdin = child.stdin.take().expect("The child's stdin is piped");
+        stdin.write_all(text.as_bytes()).map_err(|err| format!("Failed to write to '{}': {}", program, err))?;
+        // Closing stdin tells the program the text is complete.
+        drop(stdin);
+        let status = child.wait().map_err(|err| format!("Failed to run '{}': {}", program, err))?;
+        if !status.success() {
+            return Err(format!("'{}' exited with {}", program, status));
+        }
+        return Ok(());
+    }
+    let programs: Vec<&str> = CLIPBOARD_COMMANDS.iter().map(|(program, _)| *program).collect();
+    Err(format!("No clipboard tool found (tried {})", programs.join(", ")))
+}
+
+// Prints one entry of `list`, numbered by the bookmark's position in the stored config.
+// Pinned bookmarks are marked with '(pinned)', bookmarks whose directory no longer exists with '(missing)', file
+// bookmarks with '(file)' and those from the system config with '(system)'.
+fn print_bookmark(index: usize, bookmark: &Bookmark, style: ListStyle) {
+    let color = style.color;
+    let mut markers = String::new();
+    if bookmark.pinned {
+        markers.push_str(&format!(" {}", paint("(pinned)", Style::Success, color)));
+    }
+    if bookmark.is_missing() {
+        markers.push_str(&format!(" {}", paint("(missing)", Style::Warning, color)));
+    }
+    if bookmark.kind == BookmarkKind::File {
+        markers.push_str(&format!(" {}", paint("(file)", Style::Path, color)));
+    }
+    if bookmark.system {
+        markers.push_str(&format!(" {}", paint("(system)", Style::Path, color)));
+    }
+    let path = if style.relative { tilde_path(&bookmark.path) } else { bookmark.path.display().to_string() };
+    println!(
+        "{}. Alias: '{}', Path: '{}'{}",
+        index + 1,
+        paint(&bookmark.alias, Style::Alias, color),
+        paint(&path, Style::Path, color),
+        markers
+    );
+    if style.long {
+        let created_at = bookmark.created_at
+            .map(|created_at| created_at.to_rfc3339_opts(SecondsFormat::Secs, true))
+            .unwrap_or_else(|| "unknown".to_string());
+        println!("   Created: {}", created_at);
+        if !bookmark.tags.is_empty() {
+            println!("   Tags: {}", bookmark.tags.join(", "));
+        }
+        if let Some(description) = &bookmark.description {
+            println!("   Description: {}", description);
+        }
+        if let Some(on_jump) = &bookmark.on_jump {
+            println!("   On jump: {}", on_jump);
+        }
+        for path in &bookmark.alternate_paths {
+            let path = if style.relative { tilde_path(path) } else { path.display().to_string() };
+            println!("   Also at: {}", paint(&path, Style::Path, color));
+        }
+    }
+}
+
+// Resolves '.' and '..' in a path without touching the filesystem, so it works for paths that don't exist.
+// Unlike fs::canonicalize, symlinks are left alone, so '..' after one goes up from the link itself.
+fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            // '..' at the root stays at the root, as it does for the OS.
+            Component::ParentDir if normalized.file_name().is_some() => {
+                normalized.pop();
+            }
+            Component::ParentDir if normalized.has_root() => {}
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+// Returns the name of the directory a path points at, the last component once '.' and '..' are resolved, so
+// '/srv/app/..' gives 'srv'. A path that doesn't name a directory that way, like '/' or '..', is returned in full.
+fn dir_name(path: &Path) -> String {
+    let mut names = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(name) => names.push(name),
+            Component::ParentDir => {
+                names.pop();
+            }
+            _ => {}
+        }
+    }
+    match names.last() {
+        Some(name) => name.to_string_lossy().into_owned(),
+        None => path.display().to_string(),
+    }
+}
+
+// Returns the path for display with the home directory abbreviated to '~', like shells do, e.g. '~/projects'.
+// Paths outside the home directory are returned in full.
+fn tilde_path(path: &Path) -> String {
+    match dirs::home_dir().and_then(|home| path.strip_prefix(home).ok().map(Path::to_path_buf)) {
+        Some(rest) if rest.as_os_str().is_empty() => "~".to_string(),
+        Some(rest) => format!("~{}{}", std::path::MAIN_SEPARATOR, rest.display()),
+        None => path.display().to_string(),
+    }
+}
+
+// Checks whether the whole text matches a glob pattern, where '*' matches any run of characters (including none)
+// and '?' matches exactly one. Every other character matches itself.
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // Where the last '*' was seen, and where in the text it started matching, to backtrack to on a mismatch.
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                Some((star_p, star_t)) => {
+                    p = star_p + 1;
+                    t = star_t + 1;
+                    star = Some((star_p, star_t + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+// Sorts (index, bookmark) pairs in the given order without touching the stored config.
+// The sort is stable, so bookmarks that compare equal keep the order they were added in.
+fn sort_bookmarks(bookmarks: &mut [(usize, &Bookmark)], order: SortOrder) {
+    match order {
+        SortOrder::Alias => bookmarks.sort_by(|(_, a), (_, b)| a.alias.cmp(&b.alias)),
+        SortOrder::Path => bookmarks.sort_by(|(_, a), (_, b)| a.path.cmp(&b.path)),
+        SortOrder::Added => bookmarks.sort_by_key(|(index, _)| *index),
+        SortOrder::Frecency => {
+            let now = Utc::now();
+            bookmarks.sort_by(|(_, a), (_, b)| frecency(b, now).total_cmp(&frecency(a, now)));
+        }
+    }
+}
+
+// Moves pinned bookmarks ahead of the others. The sort is stable, so both groups keep their order otherwise.
+fn pinned_first(bookmarks: &mut [(usize, &Bookmark)]) {
+    bookmarks.sort_by_key(|(_, bookmark)| !bookmark.pinned);
+}
+
+// Joins the directory given with `jump --subdir` onto a bookmarked directory.
+// The subdirectory must be relative and may not contain '..', so the result can't point outside the bookmark.
+// Returns an error message if it does, or if the joined path isn't an existing directory.
+fn join_subdir(base: &Path, subdir: &Path) -> Result<PathBuf, String> {
+    if subdir.components().any(|component| !matches!(component, Component::Normal(_) | Component::CurDir)) {
+        return Err(format!("Subdirectory '{}' must be a relative path without '..'", subdir.display()));
+    }
+    let path = base.join(subdir);
+    if !path.is_dir() {
+        return Err(format!("'{}' is not an existing directory", path.display()));
+    }
+    Ok(path)
+}
+
+// Asks which bookmark to jump to, returning its index. When the finder command (see Settings::finder) is installed,
+// it does the asking. Otherwise a numbered menu of the bookmarks is shown, and the number of the one to jump to is read.
+// The menu is drawn on stderr and read from stdin, since `jump` usually runs inside the shell function's command
+// substitution where stdout is captured. Both have to be a terminal, otherwise there is nobody to ask.
+// Either way, pinned bookmarks come first, as in `list`.
+fn pick_bookmark(bookmarks: &[Bookmark], finder: &str) -> Result<usize, String> {
+    if !io::stdin().is_terminal() || !io::stderr().is_terminal() {
+        return Err("No alias given. Pass one with --alias.".to_string());
+    }
+    if bookmarks.is_empty() {
+        return Err("You have no bookmarks.".to_string());
+    }
+    let mut bookmarks: Vec<(usize, &Bookmark)> = bookmarks.iter().enumerate().collect();
+    pinned_first(&mut bookmarks);
+    if let Some(index) = run_finder(&bookmarks, finder)? {
+        return Ok(index);
+    }
+
+    for (number, (_, bookmark)) in bookmarks.iter().enumerate() {
+        eprintln!("{}. {} ({})", number + 1, bookmark.alias, bookmark.path.display());
+    }
+    eprint!("Jump to: ");
+
+    let mut input = String::new();
+    io::stdin().read_line(&mut input).map_err(|err| format!("Failed to read selection: {}", err))?;
+    match input.trim().parse::<usize>() {
+        Ok(number) if (1..=bookmarks.len()).contains(&number) => Ok(bookmarks[number - 1].0),
+        _ => Err(format!("'{}' is not a number between 1 and {}", input.trim(), bookmarks.len())),
+    }
+}
+
+// Lets the user choose a bookmark with a fuzzy finder like fzf, which is given one 'alias<TAB>path' line per
+// bookmark on stdin and prints the chosen line. Finders draw on the terminal themselves, so only stdout is captured.
+// The bookmarks are given as (index, bookmark) pairs in the order to show them, and the index of the chosen one is
+// returned. Returns Ok(None) when the finder command is empty or not installed, and an error when nothing was chosen.
+fn run_finder(bookmarks: &[(usize, &Bookmark)], finder: &str) -> Result<Option<usize>, String> {
+    let mut words = finder.split_whitespace();
+    let Some(program) = words.next() else {
+        return Ok(None);
+    };
+    let mut child = match process::Command::new(program)
+        .args(words)
+        .stdin(process::Stdio::piped())
+        .stdout(process::Stdio::piped())
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(format!("Failed to run '{}': {}", finder, err)),
+    };
+
+    let lines: Vec<String> = bookmarks
+        .iter()
+        .map(|(_, bookmark)| format!("{}\t{}", bookmark.alias, bookmark.path.display()))
+        .collect();
+    let mut stdin = child.stdin.take().expect("The finder's stdin is piped");
+    // The finder may exit before reading everything, e.g. when the user cancels right away, so a failed write is
+    // left for its exit status to report.
+    let _ = stdin.write_all(format!("{}\n", lines.join("\n")).as_bytes());
+    drop(stdin);
+
+    let output = child.wait_with_output().map_err(|err| format!("Failed to run '{}': {}", finder, err))?;
+    let selection = String::from_utf8_lossy(&output.stdout);
+    let selection = selection.lines().next().unwrap_or_default();
+    match lines.iter().position(|line| line == selection) {
+        Some(position) if output.status.success() => Ok(Some(bookmarks[position].0)),
+        _ => Err("No bookmark was selected.".to_string()),
+    }
+}
+
+// Parses a duration like `90m`, `12h`, `30d`, `2w`, `6mo` or `1y`, for `prune --older-than` (and see parse_time).
+// Months count as 30 days and years as 365, which is close enough for deciding what's gone unused.
+fn parse_duration(duration: &str) -> Result<chrono::Duration, String> {
+    let split = duration.find(|c: char| !c.is_ascii_digit()).unwrap_or(duration.len());
+    let (amount, unit) = duration.split_at(split);
+    let amount: i64 = amount
+        .parse()
+        .map_err(|_| format!("'{}' doesn't start with a number, e.g. `30d`", duration))?;
+    let days = |days: i64| chrono::Duration::try_days(amount.saturating_mul(days));
+    let parsed = match unit {
+        "m" | "min" => chrono::Duration::try_minutes(amount),
+        "h" => chrono::Duration::try_hours(amount),
+        "d" => days(1),
+        "w" => days(7),
+        "mo" => days(30),
+        "y" => days(365),
+        _ => return Err(format!("Unknown unit '{}' in '{}'. Use m, h, d, w, mo or y", unit, duration)),
+    };
+    parsed.ok_or_else(|| format!("'{}' is too long", duration))
+}
+
+// Parses a point in time for `list --since` and `--until`: an RFC 3339 time like `2024-05-01T09:00:00Z`, a date like
+// `2024-05-01` at midnight local time, or a duration before now like `30d` (see parse_duration).
+// With `end_of_day`, a date stands for the end of that day instead, so a range up to it includes the day itself.
+fn parse_time(time: &str, end_of_day: bool) -> Result<DateTime<Utc>, String> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(time) {
+        return Ok(time.with_timezone(&Utc));
+    }
+    if let Ok(date) = chrono::NaiveDate::parse_from_str(time, "%Y-%m-%d") {
+        let day = if end_of_day { date.succ_opt() } else { Some(date) };
+        return day
+            .and_then(|day| day.and_hms_opt(0, 0, 0))
+            .and_then(|midnight| midnight.and_local_timezone(chrono::Local).earliest())
+            .map(|midnight| midnight.with_timezone(&Utc))
+            .ok_or_else(|| format!("'{}' is out of range", time));
+    }
+    match parse_duration(time) {
+        Ok(duration) => Ok(Utc::now() - duration),
+        Err(_) => Err(format!("'{}' isn't a date like 2024-05-01, a time like 2024-05-01T09:00:00Z or a duration like 30d", time)),
+    }
+}
+
+// Scores a bookmark by how often and how recently it was jumped to, in the spirit of zoxide and z.
+// The visit count is weighted by the time since the last visit: recent visits count for more than old ones.
+// Bookmarks that were never visited score 0.
+fn frecency(bookmark: &Bookmark, now: DateTime<Utc>) -> f64 {
+    let Some(last_visited) = bookmark.last_visited else {
+        return 0.0;
+    };
+    let hours = (now - last_visited).num_hours();
+    let weight = match hours {
+        h if h < 1 => 4.0,
+        h if h < 24 => 2.0,
+        h if h < 24 * 7 => 0.5,
+        _ => 0.25,
+    };
+    bookmark.visits as f64 * weight
+}
+
+impl From<Shell> for clap_complete::Shell {
+    fn from(shell: Shell) -> Self {
+        match shell {
+            Shell::Bash => clap_complete::Shell::Bash,
+            Shell::Zsh => clap_complete::Shell::Zsh,
+            Shell::Fish => clap_complete::Shell::Fish,
+            Shell::Powershell => clap_complete::Shell::PowerShell,
+        }
+    }
+}
+
+// Works out the user's shell from the $SHELL environment variable.
+// Only the file name of the shell binary is considered, so '/usr/bin/zsh' and 'zsh' are both recognized.
+// Returns None when $SHELL is unset or names a shell pomelo doesn't support.
+fn detect_shell() -> Option<Shell> {
+    let shell = env::var_os("SHELL")?;
+    let name = PathBuf::from(shell).file_stem()?.to_string_lossy().to_lowercase();
+    match name.as_str() {
+        "bash" => Some(Shell::Bash),
+        "zsh" => Some(Shell::Zsh),
+        "fish" => Some(Shell::Fish),
+        "pwsh" | "powershell" => Some(Shell::Powershell),
+        _ => None,
+    }
+}
+
+// Returns the shell code defining the `j` function for the given shell.
+// The function runs `pomelo jump` and only changes directory when it succeeds, so a missing alias leaves the shell where it was.
+// Anything `jump` prints after the path is the bookmark's on-jump command, which the function runs after the `cd`.
+// It also registers completion of `j`'s argument with the aliases from `pomelo list-aliases`.
+fn init_script(shell: Shell) -> &'static str {
+    match shell {
+        Shell::Bash => r#"j() {
+    local out
+    out="$(command pomelo jump --print-path ${1:+--alias "$1"})" && cd -- "${out%%$'\n'*}" || return
+    if [[ "$out" == *$'\n'* ]]; then
+        eval "${out#*$'\n'}"
+    fi
+}
+_pomelo_j() {
+    COMPREPLY=($(command pomelo list-aliases --complete="${COMP_WORDS[COMP_CWORD]}" 2>/dev/null))
+    [[ ${#COMPREPLY[@]} -eq 1 && "${COMPREPLY[0]}" == */ ]] && compopt -o nospace
+}
+complete -F _pomelo_j j
+"#,
+        Shell::Zsh => r#"j() {
+    local out
+    out="$(command pomelo jump --print-path ${1:+--alias "$1"})" && cd -- "${out%%$'\n'*}" || return
+    if [[ "$out" == *$'\n'* ]]; then
+        eval "${out#*$'\n'}"
+    fi
+}
+_pomelo_j() {
+    local -a aliases
+    aliases=(${(f)"$(command pomelo list-aliases 2>/dev/null)"})
+    _multi_parts / aliases
+}
+(( $+functions[compdef] )) && compdef _pomelo_j j
+"#,
+        Shell::Fish => r#"function j
+    set -l args --print-path
+    set -q argv[1]; and set -a args --alias $argv[1]
+    set -l out (command pomelo jump $args); and cd -- $out[1]; or return
+    if set -q out[2]
+        eval (string join \n -- $out[2..-1])
+    end
+end
+complete -c j -f -a "(command pomelo list-aliases --complete=(commandline -ct) 2>/dev/null)"
+"#,
+        Shell::Powershell => r#"function j {
+    param([string]$Alias)
+    $pomeloArgs = @('jump', '--print-path')
+    if ($Alias) { $pomeloArgs += @('--alias', $Alias) }
+    $out = @(pomelo @pomeloArgs)
+    if ($LASTEXITCODE -ne 0) { return }
+    Set-Location -LiteralPath $out[0]
+    if ($out.Count -gt 1) { Invoke-Expression ($out[1..($out.Count - 1)] -join "`n") }
+}
+Register-ArgumentCompleter -CommandName j -ParameterName Alias -ScriptBlock {
+    param($commandName, $parameterName, $wordToComplete)
+    pomelo list-aliases "--complete=$wordToComplete" 2>$null
+}
+"#,
+    }
+}
+
+// Returns the subcommands whose --alias refers to an existing bookmark, and so get completions for the user's aliases,
+// under their names as well as their visible aliases like `resolve`. That's every subcommand with an --alias
+// except `add`, whose alias is a new one.
+fn alias_commands() -> Vec<String> {
+    Cli::command()
+        .get_subcommands()
+        .filter(|command| command.get_name() != "add")
+        .filter(|command| command.get_arguments().any(|arg| arg.get_long() == Some("alias")))
+        .flat_map(|command| std::iter::once(command.get_name()).chain(command.get_visible_aliases()).map(str::to_string).collect::<Vec<_>>())
+        .collect()
+}
+
+// Extends a completion script generated by clap_complete so `--alias` completes the user's existing aliases for the
+// subcommands from alias_commands, by asking `pomelo list-aliases` at completion time.
+// clap only knows about the static shape of the CLI, so each shell needs its own hook:
+// bash wraps the generated function, zsh gets a completer for the matching `--alias` specs, and fish just adds a rule.
+// PowerShell's generated script has no hook for argument values, so it is returned unchanged.
+fn add_alias_completions(shell: Shell, script: String) -> String {
+    let commands = alias_commands();
+    match shell {
+        Shell::Bash => format!(
+            r#"{script}
+_pomelo_aliases() {{
+    local prev="${{COMP_WORDS[COMP_CWORD-1]}}"
+    if [[ "$prev" == "-a" || "$prev" == "--alias" ]]; then
+        case "${{COMP_WORDS[1]}}" in
+            {commands})
+                COMPREPLY=($(pomelo list-aliases --complete="${{COMP_WORDS[COMP_CWORD]}}" 2>/dev/null))
+                [[ ${{#COMPREPLY[@]}} -eq 1 && "${{COMPREPLY[0]}}" == */ ]] && compopt -o nospace
+                return 0
+                ;;
+        esac
+    fi
+    _pomelo "$@"
+}}
+complete -F _pomelo_aliases -o bashdefault -o default pomelo
+"#,
+            commands = commands.join("|"),
+        ),
+        Shell::Zsh => {
+            let mut output = String::new();
+            let mut command = "";
+            for line in script.lines() {
+                if let Some(name) = line.strip_prefix('(').and_then(|rest| rest.strip_suffix(')')) {
+                    command = name;
+                }
+                if line == r#"if [ "$funcstack[1]" = "_pomelo" ]; then"# {
+                    output.push_str(
+                        r#"(( $+functions[_pomelo_aliases] )) ||
+_pomelo_aliases() {
+    local -a aliases
+    aliases=(${(f)"$(pomelo list-aliases 2>/dev/null)"})
+    _multi_parts "$@" / aliases
+}
+
+"#,
+                    );
+                }
+                // Specs of repeatable options start with '*'.
+                let spec = line.trim_start_matches('\'').trim_start_matches('*');
+                let is_alias_spec = spec.starts_with("-a+[") || spec.starts_with("--alias=[");
+                if is_alias_spec && commands.iter().any(|name| name == command) {
+                    output.push_str(&line.replace(":ALIAS: '", ":ALIAS:_pomelo_aliases'"));
+                } else {
+                    output.push_str(line);
+                }
+                output.push('\n');
+            }
+            output
+        }
+        Shell::Fish => format!(
+            "{}complete -c pomelo -n \"__fish_seen_subcommand_from {}\" -s a -l alias -f -a \"(pomelo list-aliases --complete=(commandline -ct) 2>/dev/null)\"\n",
+            script,
+            commands.join(" "),
+        ),
+        Shell::Powershell => script,
+    }
+}
+
+fn main() {
+    let cli = Cli::parse();
+    let format = cli.format;
+    let color = use_color(cli.no_color);
+    let verbosity = match (cli.quiet, cli.verbose) {
+        (true, _) => Verbosity::Quiet,
+        (_, true) => Verbosity::Verbose,
+        _ => Verbosity::Normal,
+    };
+    let preview_on_stderr = matches!(cli.command, Commands::Jump { .. });
+    let save = SaveOptions { format, verbosity, dry_run: cli.dry_run, no_save: cli.no_save, preview_on_stderr };
+
+    // Shell startup runs these, so they must work whatever state the config is in, and even without one.
+    match cli.command {
+        Commands::Init { shell } => {
+            let Some(shell) = shell.or_else(detect_shell) else {
+                fail(format, "Could not detect your shell from $SHELL. Pass it explicitly, e.g. 'pomelo init zsh'.");
+            };
+            print!("{}", init_script(shell));
+            return;
+        }
+        Commands::Completions { shell } => {
+            let mut script = Vec::new();
+            clap_complete::generate(clap_complete::Shell::from(shell), &mut Cli::command(), "pomelo", &mut script);
+            let script = String::from_utf8(script).expect("Completion script is not valid UTF-8");
+            print!("{}", add_alias_completions(shell, script));
+            return;
+        }
+        _ => {}
+    }
+
+    if let Some(profile) = &cli.profile {
+        if let Err(err) = validate_profile(profile) {
+            fail(format, err);
+        }
+    }
+    let config_path = match get_config_path(cli.config.as_deref(), cli.profile.as_deref()) {
+        Ok(config_path) => config_path,
+        Err(err) => fail(format, err),
+    };
+
+    trace(verbosity, format!("Using config file '{}'", config_path.display()));
+
+    // Held until main returns, across the whole read-modify-write cycle. Commands that only read the config don't
+    // wait for it, since every write replaces the file in one rename (see write_config).
+    let take_lock = || match lock_config(&config_path) {
+        Ok(lock) => lock,
+        Err(err) => fail(format, err),
+    };
+    let mut lock = if cli.command.locks_config() { take_lock() } else { None };
+
+    migrate_legacy_config(&config_path);
+
+    // Finding the file is what you need when it doesn't load, so this doesn't read it.
+    if let Commands::Config { command: ConfigCommand::Path } = cli.command {
+        match format {
+            OutputFormat::Text => println!("{}", config_path.display()),
+            OutputFormat::Json => println!("{}", json!({ "action": "path", "path": config_path })),
+        }
+        return;
+    }
+
+    // `doctor` has to work on configs that fail to load, so it reads the file itself.
+    if let Commands::Doctor = cli.command {
+        let checks = diagnose(&config_path);
+        if format == OutputFormat::Json {
+            let checks: Vec<serde_json::Value> = checks
+                .iter()
+                .map(|(status, message)| json!({ "status": format!("{:?}", status).to_lowercase(), "message": message }))
+                .collect();
+            println!("{}", serde_json::to_string_pretty(&checks).expect("Failed to serialize checks"));
+        } else {
+            for (status, message) in &checks {
+                println!("{} {}", paint(status.marker(), status.style(), color), message);
+            }
+        }
+        // --strict fails on a warning too.
+        if checks.iter().any(|(status, _)| *status == CheckStatus::Fail || (cli.strict && *status == CheckStatus::Warn)) {
+            process::exit(1);
+        }
+        return;
+    }
+
+    // These replace or commit files wholesale instead of saving a changed config, so there is nothing to preview.
+    if save.discards_changes()
+        && matches!(
+            cli.command,
+            Commands::Convert { .. } | Commands::Restore | Commands::Undo | Commands::Sync { .. } | Commands::Config { command: ConfigCommand::Edit }
+        )
+    {
+        fail(format, "--dry-run and --no-save aren't supported by this command");
+    }
+
+    // Failing now, before anything is reported, beats saying a change was made and then failing to save it.
+    if cli.command.modifies_config() && !save.discards_changes() {
+        if let Err(err) = check_writable(&config_path) {
+            fail(format, err);
+        }
+    }
+
+    // The backup is what brings back a config that fails to load, so `restore` doesn't load it either.
+    if let Commands::Restore = cli.command {
+        let backup_path = get_backup_path(&config_path);
+        if !backup_path.exists() {
+            fail(format, format!("No backup found at '{}'", backup_path.display()));
+        }
+
+        let tmp_path = with_suffix(&config_path, ".tmp");
+        if config_path.exists() {
+            fs::rename(&config_path, &tmp_path).expect("Failed to move config file aside");
+        }
+        fs::rename(&backup_path, &config_path).expect("Failed to restore backup");
+        if tmp_path.exists() {
+            fs::rename(&tmp_path, &backup_path).expect("Failed to keep the replaced config as backup");
+        }
+        say(verbosity, format!("Restored config from '{}'", backup_path.display()));
+        return;
+    }
+
+    // A config that fails to load is the one most in need of editing, so this doesn't load it.
+    if let Commands::Config { command: ConfigCommand::Edit } = cli.command {
+        if !config_path.exists() {
+            let empty = Config { version: CONFIG_VERSION, settings: Settings::default(), bookmarks: Vec::new(), unknown: BTreeMap::new() };
+            write_config(&empty, &config_path, save);
+        }
+        // Editing a config that's already broken shouldn't replace a backup that still works.
+        if read_config(&config_path).is_ok() {
+            backup_config(&config_path, save);
+        }
+        // The editor stays open for as long as the user likes, which shouldn't keep other commands waiting.
+        drop(lock.take());
+        if let Err(err) = run_editor(&config_path) {
+            fail(format, err);
+        }
+        match read_config(&config_path) {
+            Ok(config) => say(verbosity, format!("Saved '{}' with {} bookmark(s).", config_path.display(), config.bookmarks.len())),
+            Err(err) => fail(
+                format,
+                format!("{}\nThe config from before the edit is kept at '{}'. Run `pomelo restore` to bring it back.", err, get_backup_path(&config_path).display()),
+            ),
+        }
+        return;
+    }
+
+    let mut config = match load_or_initialize_config(&config_path, save, cli.strict) {
+        Ok(config) => config,
+        Err(err) => fail(format, err),
+    };
+
+    match &cli.command {
+        Commands::Add { alias, path, force, allow_missing, update, no_canonicalize, tags, description, on_jump, append, expand_on_jump, file } => {
+            let canonicalize = !no_canonicalize;
+            let is_file = |path: &Path| *file || expand_path(path).is_ok_and(|path| path.is_file());
+            let kind = match path {
+                Some(path) if is_file(path) => BookmarkKind::File,
+                _ => BookmarkKind::Dir,
+            };
+            let path = match path {
+                // A template is checked against the current environment, but saved unexpanded.
+                Some(path) if *expand_on_jump && *allow_missing => Ok(path.clone()),
+                Some(path) if *expand_on_jump => {
+                    expand_path(path).and_then(|expanded| resolve_bookmark_path(&expanded, kind, false, canonicalize)).map(|_| path.clone())
+                }
+                Some(path) => expand_path(path).and_then(|path| resolve_bookmark_path(&path, kind, *allow_missing, canonicalize)),
+                None => {
+                    let current_dir = env::current_dir().expect("Failed to get current directory");
+                    // The OS reports the current directory with symlinks resolved, but the shell's $PWD keeps the
+                    // path that was `cd`-ed into, as long as it still refers to the same directory.
+                    let current_dir = env::var_os("PWD")
+                        .map(PathBuf::from)
+                        .filter(|pwd| !canonicalize && fs::canonicalize(pwd).is_ok_and(|pwd| pwd == current_dir))
+                        .unwrap_or(current_dir);
+                    resolve_bookmark_path(&current_dir, kind, *allow_missing, canonicalize)
+                }
+            };
+            let path = match path {
+                Ok(path) => path,
+                Err(err) => fail(format, err),
+            };
+            trace(verbosity, format!("Resolved the path to '{}'", path.display()));
+            // A file bookmark is named without its extension, so 'notes.md' becomes 'notes'.
+            let name = if kind == BookmarkKind::File { path.file_stem() } else { path.file_name() };
+            let alias = match (alias, name.map(alias_from_name)) {
+                (Some(alias), _) => alias.clone(),
+                (None, Some(name)) if !name.is_empty() => name,
+                (None, _) => fail(format, format!("Can't derive an alias from '{}'. Pass one with --alias.", path.display())),
+            };
+            if let Err(err) = validate_alias(&alias) {
+                fail(format, err);
+            }
+
+            if *append {
+                let Some(bookmark) = config.bookmarks.iter_mut().find(|b| b.alias == alias) else {
+                    fail(format, format!("No bookmark found with alias '{}' to add the path to", alias));
+                };
+                if let Err(err) = bookmark.check_changeable() {
+                    fail(format, err);
+                }
+                if bookmark.path == path || bookmark.alternate_paths.contains(&path) {
+                    fail(format, format!("'{}' is already a path of '{}'", path.display(), alias));
+                }
+                if bookmark.kind != kind {
+                    let what = if bookmark.kind == BookmarkKind::File { "a file" } else { "a directory" };
+                    fail(format, format!("'{}' bookmarks {}, so '{}' can't be another path of it", alias, what, path.display()));
+                }
+                bookmark.alternate_paths.push(path.clone());
+                let message = format!("Added '{}' to the paths of '{}'", path.display(), alias);
+                report(format, verbosity, &message, json!({ "action": "append", "alias": alias, "path": path }));
+                save_config(&config, &config_path, save);
+                return;
+            }
+
+            let canonical = canonical_path(&path);
+            let existing = config.bookmarks
+                .iter()
+                .find(|b| b.alias != alias && canonical_path(&b.path) == canonical)
+                .map(|b| b.alias.clone());
+            if let (true, Some(old_alias)) = (*update, existing) {
+                if let Some(taken) = config.bookmarks.iter().position(|b| b.alias == alias) {
+                    if !force {
+                        fail(format, format!("A bookmark with alias '{}' already exists. Use --force to replace it.", alias));
+                    }
+                    config.bookmarks.remove(taken);
+                }
+
+                let bookmark = config.bookmarks.iter_mut().find(|b| b.alias == old_alias).expect("Bookmark disappeared");
+                if let Err(err) = bookmark.check_changeable() {
+                    fail(format, err);
+                }
+                bookmark.alias = alias.clone();
+                if !tags.is_empty() {
+                    bookmark.tags = tags.clone();
+                }
+                if description.is_some() {
+                    bookmark.description = description.clone();
+                }
+                if on_jump.is_some() {
+                    bookmark.on_jump = on_jump.clone();
+                }
+                let message = format!("Updated bookmark '{}' to alias '{}'", old_alias, alias);
+                report(format, verbosity, &message, json!({ "action": "rename", "alias": alias, "previous_alias": old_alias, "path": bookmark.path }));
+                save_config(&config, &config_path, save);
+                return;
+            }
+
+            // Bookmarking a directory twice can be intentional, so this only warns, unless --strict says otherwise.
+            for other in config.bookmarks.iter().filter(|b| b.alias != alias && canonical_path(&b.path) == canonical) {
+                if cli.strict {
+                    fail(format, format!("'{}' is already bookmarked as '{}'", path.display(), other.alias));
+                }
+                eprintln!("Warning: '{}' is already bookmarked as '{}'", path.display(), other.alias);
+            }
+
+            if let Some(bookmark) = config.bookmarks.iter_mut().find(|b| b.alias == alias) {
+                if !force {
+                    fail(format, format!("A bookmark with alias '{}' already exists. Use --force to overwrite it.", alias));
+                }
+                let message = format!("Updated bookmark with alias '{}'", alias);
+                report(format, verbosity, &message, json!({ "action": "update", "alias": alias, "path": path }));
+                bookmark.path = path;
+                bookmark.alternate_paths.clear();
+                // Replacing a system bookmark makes it the user's own, which overrides the system one from now on.
+                bookmark.system = false;
+                bookmark.expand_on_jump = *expand_on_jump;
+                bookmark.kind = kind;
+                if !tags.is_empty() {
+                    bookmark.tags = tags.clone();
+                }
+                if description.is_some() {
+                    bookmark.description = description.clone();
+                }
+                if on_jump.is_some() {
+                    bookmark.on_jump = on_jump.clone();
+                }
+                save_config(&config, &config_path, save);
+            } else {
+                let message = format!("Added bookmark with alias '{}'", alias);
+                report(format, verbosity, &message, json!({ "action": "add", "alias": alias, "path": path }));
+                let mut bookmark = Bookmark::new(alias.clone(), path);
+                bookmark.tags = tags.clone();
+                bookmark.description = description.clone();
+                bookmark.on_jump = on_jump.clone();
+                bookmark.expand_on_jump = *expand_on_jump;
+                bookmark.kind = kind;
+                config.bookmarks.push(bookmark);
+                save_config(&config, &config_path, save);
+            }
+        }
+        Commands::BulkAdd { file, allow_missing } => {
+            let contents = match file.as_deref().filter(|file| *file != Path::new("-")) {
+                Some(file) => fs::read_to_string(file).map_err(|err| format!("Failed to read '{}': {}", file.display(), err)),
+                None => io::read_to_string(io::stdin()).map_err(|err| format!("Failed to read stdin: {}", err)),
+            };
+            let contents = match contents {
+                Ok(contents) => contents,
+                Err(err) => fail(format, err),
+            };
+
+            let (mut added, mut skipped, mut errors) = (0, 0, 0);
+            for (number, line) in contents.lines().enumerate() {
+                let line = line.trim();
+                if line.is_empty() || line.starts_with('#') {
+                    continue;
+                }
+                let result = line
+                    .split_once('\t')
+                    .or_else(|| line.split_once('='))
+                    .ok_or_else(|| "expected 'alias<TAB>path' or 'alias=path'".to_string())
+                    .and_then(|(alias, path)| {
+                        let alias = alias.trim();
+                        validate_alias(alias)?;
+                        let path = expand_path(Path::new(path.trim()))?;
+                        Ok((alias, resolve_bookmark_path(&path, BookmarkKind::Dir, *allow_missing, true)?))
+                    });
+                match result {
+                    Ok((alias, _)) if config.bookmarks.iter().any(|b| b.alias == alias) => {
+                        say(verbosity, format!("Skipped '{}': a bookmark with this alias already exists", alias));
+                        skipped += 1;
+                    }
+                    Ok((alias, path)) => {
+                        trace(verbosity, format!("Added '{}' for '{}'", alias, path.display()));
+                        config.bookmarks.push(Bookmark::new(alias.to_string(), path));
+                        added += 1;
+                    }
+                    Err(err) => {
+                        eprintln!("Line {}: {}", number + 1, err);
+                        errors += 1;
+                    }
+                }
+            }
+
+            if added > 0 {
+                save_config(&config, &config_path, save);
+            }
+            let message = format!("Added {}, skipped {} existing, {} failed.", added, skipped, errors);
+            report(format, verbosity, &message, json!({ "action": "bulk-add", "added": added, "skipped": skipped, "failed": errors }));
+            if errors > 0 {
+                process::exit(1);
+            }
+        }
+        Commands::Remove { aliases, ignore_case } => {
+           let (mut removed, mut failed) = (0, 0);
+           for alias in aliases {
+            match find_bookmark(&config.bookmarks, alias, *ignore_case) {
+                Ok(index) if config.bookmarks[index].system => {
+                    report_error(format, config.bookmarks[index].check_changeable().unwrap_err());
+                    failed += 1;
+                }
+                Ok(index) => {
+                    let bookmark = config.bookmarks.remove(index);
+                    let message = format!("Removed bookmark with alias '{}'", bookmark.alias);
+                    report(format, verbosity, &message, json!({ "action": "remove", "alias": bookmark.alias, "path": bookmark.path }));
+                    removed += 1;
+                }
+                Err(candidates) if candidates.is_empty() => {
+                    report_error(format, format!("No bookmark found with alias '{}'", alias));
+                    failed += 1;
+                }
+                Err(candidates) => {
+                    let matches: Vec<&str> = candidates.iter().map(|b| b.alias.as_str()).collect();
+                    report_error(format, format!("Alias '{}' is ambiguous, it matches: {}", alias, matches.join(", ")));
+                    failed += 1;
+                }
+            }
+           }
+
+           // The aliases that were found are removed even when others weren't, but the exit status reports the miss.
+           if removed > 0 {
+            backup_config(&config_path, save);
+            save_config(&config, &config_path, save);
+           }
+           if failed > 0 {
+            process::exit(1);
+           }
+        }
+        Commands::Edit { alias, new, path, allow_missing, description, on_jump, ignore_case } => {
+            if let Some(Err(err)) = new.as_deref().map(validate_alias) {
+                fail(format, err);
+            }
+            // A new path has to be the same kind of thing as the bookmark's current one.
+            let kind = find_bookmark(&config.bookmarks, alias, *ignore_case).map_or(BookmarkKind::Dir, |index| config.bookmarks[index].kind);
+            let path = path.as_ref().map(|path| match expand_path(path).and_then(|path| resolve_bookmark_path(&path, kind, *allow_missing, true)) {
+                Ok(path) => path,
+                Err(err) => fail(format, err),
+            });
+            if let Some(path) = &path {
+                trace(verbosity, format!("Resolved the path to '{}'", path.display()));
+            }
+
+            match find_bookmark(&config.bookmarks, alias, *ignore_case) {
+                Ok(index) => {
+                    // Like rename without --force, this never replaces another bookmark.
+                    let current = &config.bookmarks[index].alias;
+                    if let Some(new) = new.as_ref().filter(|new| *new != current) {
+                        if config.bookmarks.iter().any(|b| b.alias == *new) {
+                            fail(
+                                format,
+                                format!("A bookmark with alias '{}' already exists. Use `pomelo rename --force -a {} -n {}` to replace it.", new, current, new),
+                            );
+                        }
+                    }
+                    let bookmark = &mut config.bookmarks[index];
+                    if let Err(err) = bookmark.check_changeable() {
+                        fail(format, err);
+                    }
+                    let old_alias = bookmark.alias.clone();
+                    let mut messages = Vec::new();
+                    if let Some(path) = path {
+                        messages.push(format!("Updated path of '{}' to '{}'", bookmark.alias, path.display()));
+                        bookmark.path = path;
+                    }
+                    if let Some(new) = new {
+                        messages.push(format!("Updated alias '{}' to '{}'", bookmark.alias, new));
+                        bookmark.alias = new.clone();
+                    }
+                    if let Some(description) = description {
+                        if description.is_empty() {
+                            messages.push(format!("Removed description of '{}'", bookmark.alias));
+                            bookmark.description = None;
+                        } else {
+                            messages.push(format!("Updated description of '{}'", bookmark.alias));
+                            bookmark.description = Some(description.clone());
+                        }
+                    }
+                    if let Some(on_jump) = on_jump {
+                        if on_jump.is_empty() {
+                            messages.push(format!("Removed the on-jump command of '{}'", bookmark.alias));
+                            bookmark.on_jump = None;
+                        } else {
+                            messages.push(format!("Updated the on-jump command of '{}'", bookmark.alias));
+                            bookmark.on_jump = Some(on_jump.clone());
+                        }
+                    }
+                    report(format, verbosity, &messages.join("\n"), json!({
+                        "action": "edit",
+                        "alias": bookmark.alias,
+                        "previous_alias": old_alias,
+                        "path": bookmark.path,
+                    }));
+                }
+                Err(candidates) if candidates.is_empty() => fail(format, format!("No bookmark found with alias '{}'", alias)),
+                Err(candidates) => {
+                    let aliases: Vec<&str> = candidates.iter().map(|b| b.alias.as_str()).collect();
+                    fail(format, format!("Alias '{}' is ambiguous, it matches: {}", alias, aliases.join(", ")));
+                }
+            }
+
+            save_config(&config, &config_path, save);
+        }
+        Commands::Rename { alias, new, force } => {
+            if let Err(err) = validate_alias(new) {
+                fail(format, err);
+            }
+            match config.bookmarks.iter().find(|b| b.alias == *alias).map(Bookmark::check_changeable) {
+                None => fail(format, format!("No bookmark found with alias '{}'", alias)),
+                Some(Err(err)) => fail(format, err),
+                Some(Ok(())) => {}
+            }
+            if alias != new {
+                if let Some(existing) = config.bookmarks.iter().position(|b| b.alias == *new) {
+                    if !force {
+                        fail(format, format!("A bookmark with alias '{}' already exists. Use --force to replace it.", new));
+                    }
+                    config.bookmarks.remove(existing);
+                }
+            }
+
+            // Removing the replaced bookmark may have shifted the one being renamed.
+            let bookmark = config.bookmarks.iter_mut().find(|b| b.alias == *alias).expect("Bookmark disappeared");
+            bookmark.alias = new.clone();
+            let message = format!("Renamed '{}' to '{}'", alias, new);
+            report(format, verbosity, &message, json!({ "action": "rename", "alias": new, "previous_alias": alias, "path": bookmark.path }));
+            save_config(&config, &config_path, save);
+        }
+        Commands::Move { alias, to } => {
+            if config.settings.sort_on_save {
+                fail(format, "Bookmarks are kept sorted by alias because sort_on_save is set, so they can't be moved.");
+            }
+            let Some(from) = config.bookmarks.iter().position(|b| b.alias == *alias) else {
+                fail(format, format!("No bookmark found with alias '{}'", alias));
+            };
+            if let Err(err) = config.bookmarks[from].check_changeable() {
+                fail(format, err);
+            }
+            // System bookmarks always come after the user's own, so only those have positions to move to.
+            let count = config.bookmarks.iter().filter(|b| !b.system).count();
+            if *to == 0 || *to > count {
+                fail(format, format!("Position {} is out of range, it must be between 1 and {}", to, count));
+            }
+
+            let bookmark = config.bookmarks.remove(from);
+            config.bookmarks.insert(to - 1, bookmark);
+            let message = format!("Moved '{}' from position {} to {}", alias, from + 1, to);
+            report(format, verbosity, &message, json!({ "action": "move", "alias": alias, "from": from + 1, "to": to }));
+            save_config(&config, &config_path, save);
+        }
+        Commands::List { sort, reverse, json, long, tag, group, only_broken, since, until, r#match, relative, limit, format_string } => {
+            let template = match format_string.as_deref().map(parse_template) {
+                Some(Ok(template)) => Some(template),
+                Some(Err(err)) => fail(format, err),
+                None => None,
+            };
+            // Numbers always refer to the stored order, whatever order the bookmarks are shown in.
+            let mut bookmarks: Vec<(usize, &Bookmark)> = config.bookmarks
+                .iter()
+                .enumerate()
+                .filter(|(_, bookmark)| tag.as_ref().is_none_or(|tag| bookmark.tags.contains(tag)))
+                .filter(|(_, bookmark)| group.as_ref().is_none_or(|group| in_group(&bookmark.alias, group)))
+                .filter(|(_, bookmark)| !only_broken || bookmark.is_missing())
+                .filter(|(_, bookmark)| since.is_none_or(|since| bookmark.created_at.is_some_and(|created_at| created_at >= since)))
+                .filter(|(_, bookmark)| until.is_none_or(|until| bookmark.created_at.is_some_and(|created_at| created_at < until)))
+                .filter(|(_, bookmark)| r#match.as_ref().is_none_or(|pattern| glob_match(pattern, &bookmark.alias)))
+                .collect();
+            sort_bookmarks(&mut bookmarks, sort.unwrap_or(config.settings.default_sort));
+            if *reverse {
+                bookmarks.reverse();
+            }
+            pinned_first(&mut bookmarks);
+            if *limit > 0 {
+                bookmarks.truncate(*limit);
+            }
+            let nothing_listed = bookmarks.is_empty();
+
+            if *json || format == OutputFormat::Json {
+                let bookmarks: Vec<&Bookmark> = bookmarks.into_iter().map(|(_, bookmark)| bookmark).collect();
+                println!("{}", serde_json::to_string_pretty(&bookmarks).expect("Failed to serialize bookmarks"));
+            } else if let Some(template) = &template {
+                for (index, bookmark) in bookmarks {
+                    println!("{}", render_template(template, index, bookmark, *relative));
+                }
+            } else if bookmarks.is_empty() {
+                let broken = if *only_broken { " broken" } else { "" };
+                match (tag, group, r#match) {
+                    _ if since.is_some() || until.is_some() => println!("No{} bookmarks were added in that time.", broken),
+                    (_, _, Some(pattern)) => println!("No{} bookmarks match '{}'.", broken, pattern),
+                    (Some(tag), _, None) => println!("You have no{} bookmarks tagged '{}'.", broken, tag),
+                    (None, Some(group), None) => println!("You have no{} bookmarks in '{}'.", broken, group),
+                    (None, None, None) => println!("You have no{} bookmarks.", broken),
+                }
+            } else {
+                println!("Your bookmarks:");
+                for (index, bookmark) in bookmarks {
+                    print_bookmark(index, bookmark, ListStyle { long: *long, color, relative: *relative });
+                }
+            }
+            if cli.strict && nothing_listed {
+                process::exit(1);
+            }
+        }
+        Commands::Recent { limit, json } => {
+            let mut bookmarks: Vec<(usize, &Bookmark)> = config.bookmarks
+                .iter()
+                .enumerate()
+                .filter(|(_, bookmark)| bookmark.last_visited.is_some())
+                .collect();
+            bookmarks.sort_by_key(|(_, bookmark)| std::cmp::Reverse(bookmark.last_visited));
+            bookmarks.truncate(*limit);
+
+            if *json || format == OutputFormat::Json {
+                let bookmarks: Vec<&Bookmark> = bookmarks.into_iter().map(|(_, bookmark)| bookmark).collect();
+                println!("{}", serde_json::to_string_pretty(&bookmarks).expect("Failed to serialize bookmarks"));
+            } else if bookmarks.is_empty() {
+                println!("You haven't jumped to any bookmark yet.");
+            } else {
+                println!("Recently visited bookmarks:");
+                for (index, bookmark) in bookmarks {
+                    print_bookmark(index, bookmark, ListStyle { color, ..ListStyle::default() });
+                }
+            }
+        }
+        Commands::Search { query, alias_only, path_only } => {
+            let needle = query.to_lowercase();
+            let matches: Vec<(usize, &Bookmark)> = config.bookmarks
+                .iter()
+                .enumerate()
+                .filter(|(_, bookmark)| {
+                    let alias_matches = !path_only && bookmark.alias.to_lowercase().contains(&needle);
+                    let path_matches = !alias_only && bookmark.path.to_string_lossy().to_lowercase().contains(&needle);
+                    let description_matches = !alias_only && !path_only && bookmark.description
+                        .as_ref()
+                        .is_some_and(|description| description.to_lowercase().contains(&needle));
+                    alias_matches || path_matches || description_matches
+                })
+                .collect();
+
+            if matches.is_empty() {
+                fail(format, format!("No bookmarks match '{}'", query));
+            }
+            for (index, bookmark) in matches {
+                print_bookmark(index, bookmark, ListStyle { color, ..ListStyle::default() });
+            }
+        }
+        Commands::Jump { alias, index, ancestor, parent, no_hooks, prune_missing, print_path, shell_escape, back, exact, ignore_case, subdir, create, path, open_file } => {
+            let back = *back || alias.as_deref() == Some("-");
+            let last_dir_path = get_last_dir_path(&config_path);
+            let (target, hook) = if back {
+                let last_dir = match fs::read_to_string(&last_dir_path) {
+                    Ok(last_dir) => PathBuf::from(last_dir.trim_end_matches('\n')),
+                    Err(err) if err.kind() == io::ErrorKind::NotFound => fail(format, "There is no previous directory to go back to yet."),
+                    Err(err) => fail(format, format!("Failed to read '{}': {}", last_dir_path.display(), err)),
+                };
+                if !last_dir.is_dir() {
+                    fail(format, format!("The previous directory '{}' no longer exists", last_dir.display()));
+                }
+                (last_dir, None)
+            } else {
+                let index = match (alias, index) {
+                    _ if *ancestor => {
+                        let current_dir = env::current_dir().expect("Failed to get current directory");
+                        match find_ancestor_bookmark(&config.bookmarks, &current_dir) {
+                            Some(index) => index,
+                            None => fail(format, format!("No bookmarked directory is above '{}'", current_dir.display())),
+                        }
+                    }
+                    (_, Some(index)) => {
+                        let count = config.bookmarks.len();
+                        if *index == 0 || *index > count {
+                            fail(format, format!("Index {} is out of range, it must be between 1 and {}", index, count));
+                        }
+                        index - 1
+                    }
+                    (Some(alias), None) => match find_jump_target(&config.bookmarks, alias, *exact, *ignore_case, &config.settings) {
+                        Ok(index) => index,
+                        Err(candidates) if candidates.is_empty() && *create => {
+                            let dir = match expand_path(path.as_deref().unwrap_or(Path::new(alias))) {
+                                Ok(dir) if dir.is_dir() => dir,
+                                Ok(dir) => fail(format, format!("No bookmark found with alias '{}', and '{}' is not a directory to bookmark", alias, dir.display())),
+                                Err(err) => fail(format, err),
+                            };
+                            let dir = match resolve_bookmark_path(&dir, BookmarkKind::Dir, false, true) {
+                                Ok(dir) => dir,
+                                Err(err) => fail(format, err),
+                            };
+                            // An alias like '../api' can't be used as is, so the bookmark is named after the directory.
+                            let new_alias = match validate_alias(alias) {
+                                Ok(()) => alias.clone(),
+                                Err(_) => dir.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default(),
+                            };
+                            if let Err(err) = validate_alias(&new_alias) {
+                                fail(format, err);
+                            }
+                            if config.bookmarks.iter().any(|b| b.alias == new_alias) {
+                                fail(format, format!("A bookmark with alias '{}' already exists", new_alias));
+                            }
+                            // stdout is reserved for the path, so this goes to stderr.
+                            if verbosity > Verbosity::Quiet {
+                                eprintln!("Added bookmark with alias '{}' ({})", new_alias, dir.display());
+                            }
+                            config.bookmarks.push(Bookmark::new(new_alias, dir));
+                            save_config(&config, &config_path, save);
+                            config.bookmarks.len() - 1
+                        }
+                        Err(candidates) if candidates.is_empty() => fail(format, format!("No bookmark found with alias '{}'", alias)),
+                        Err(candidates) => {
+                            let mut message = format!("Alias '{}' is ambiguous. Did you mean one of these?", alias);
+                            for bookmark in candidates {
+                                message.push_str(&format!("\n  {} ({})", bookmark.alias, bookmark.path.display()));
+                            }
+                            fail(format, message);
+                        }
+                    },
+                    (None, None) => {
+                        // Likewise the finder or menu, so the lock is let go meanwhile, and the config read again
+                        // once it's back, in case another command changed it.
+                        drop(lock.take());
+                        let picked = match pick_bookmark(&config.bookmarks, &config.settings.finder) {
+                            Ok(index) => config.bookmarks[index].alias.clone(),
+                            Err(err) => fail(format, err),
+                        };
+                        lock = take_lock();
+                        config = match load_or_initialize_config(&config_path, save, cli.strict) {
+                            Ok(config) => config,
+                            Err(err) => fail(format, err),
+                        };
+                        match config.bookmarks.iter().position(|b| b.alias == picked) {
+                            Some(index) => index,
+                            None => fail(format, format!("'{}' was removed while you were choosing", picked)),
+                        }
+                    }
+                };
+
+                let path = match config.bookmarks[index].target() {
+                    Ok(path) => path,
+                    Err(err) => fail(format, err),
+                };
+                if cli.strict {
+                    if let Some(primary) = config.bookmarks[index].primary_target().ok().filter(|primary| *primary != path) {
+                        fail(format, format!("'{}' no longer exists, so the jump would go to '{}' instead", primary.display(), path.display()));
+                    }
+                }
+                // Handing the shell a path that's gone would only make its `cd` fail with a less helpful message.
+                if !path.exists() {
+                    let message = format!("'{}' no longer exists", path.display());
+                    if *prune_missing || config.settings.prune_on_missing {
+                        let bookmark = config.bookmarks.remove(index);
+                        save_config(&config, &config_path, save);
+                        fail(format, format!("{}, so the bookmark '{}' was removed", message, bookmark.alias));
+                    }
+                    fail(format, format!("{}. Run `pomelo prune` to remove bookmarks like this.", message));
+                }
+                if let Err(err) = config.bookmarks[index].check_kind(&path) {
+                    fail(format, err);
+                }
+
+                let bookmark = &mut config.bookmarks[index];
+                // The shell can only `cd` into a directory, so a file bookmark goes to the directory it's in.
+                let (path, file) = match bookmark.kind {
+                    BookmarkKind::Dir if *open_file => fail(format, format!("'{}' bookmarks a directory, not a file to open", bookmark.alias)),
+                    BookmarkKind::Dir => (path, None),
+                    BookmarkKind::File if subdir.is_some() => fail(format, format!("'{}' bookmarks a file, so it has no --subdir", bookmark.alias)),
+                    BookmarkKind::File => (path.parent().map(Path::to_path_buf).unwrap_or_default(), Some(path)),
+                };
+                if let Some(file) = file.filter(|_| *open_file) {
+                    // The application keeps running on its own, and stdout is reserved for the path.
+                    let launched = process::Command::new(FILE_MANAGER).arg(&file).stdout(process::Stdio::null()).spawn();
+                    if let Err(err) = launched {
+                        fail(format, format!("Failed to launch '{}': {}", FILE_MANAGER, err));
+                    }
+                }
+                let target = match subdir {
+                    Some(subdir) => match join_subdir(&path, subdir) {
+                        Ok(target) => target,
+                        Err(err) => fail(format, err),
+                    },
+                    None => path,
+                };
+                let target = match (parent, target.parent()) {
+                    (false, _) => target,
+                    (true, Some(parent)) if parent.is_dir() => parent.to_path_buf(),
+                    (true, Some(parent)) => fail(format, format!("'{}' is not an existing directory", parent.display())),
+                    (true, None) => fail(format, format!("'{}' has no parent directory", target.display())),
+                };
+                let hook = bookmark.on_jump.clone().filter(|_| !no_hooks);
+                // A jump only updates statistics, so it shouldn't replace the change `undo` would revert. A read-only
+                // config shouldn't stop the jump either, it just doesn't record the visit. Visits to system
+                // bookmarks aren't recorded at all, since they aren't saved anywhere.
+                if !bookmark.system {
+                    bookmark.visits += 1;
+                    bookmark.last_visited = Some(Utc::now());
+                    if check_writable(&config_path).is_ok() {
+                        write_config(&config, &config_path, save);
+                    }
+                }
+                (target, hook)
+            };
+
+            // Remembering where the jump started is a convenience, so failing to do so shouldn't stop the jump.
+            if let Ok(current_dir) = env::current_dir() {
+                let _ = fs::write(&last_dir_path, format!("{}\n", current_dir.display()));
+            }
+            // stdout carries nothing but the path so the shell function can `cd` into it, followed by the bookmark's
+            // on-jump command for the shell function to run afterwards.
+            println!("{}", display_path(&target, *shell_escape));
+            if let Some(hook) = hook {
+                println!("{}", hook);
+            }
+            if !print_path && verbosity > Verbosity::Quiet && io::stdout().is_terminal() {
+                eprintln!("Hint: pomelo can't change your shell's directory on its own. Set up the `j` shell function with `pomelo init`.");
+            }
+        }
+        Commands::Which { alias, shell_escape, canonical, name_only } => {
+            let Some(bookmark) = config.bookmarks.iter().find(|b| b.alias == *alias) else {
+                fail(format, format!("No bookmark found with alias '{}'", alias));
+            };
+            let path = match bookmark.target() {
+                Ok(path) if *canonical => match fs::canonicalize(&path) {
+                    Ok(path) => path,
+                    Err(err) => fail(format, format!("Failed to resolve '{}': {}", path.display(), err)),
+                },
+                Ok(path) => path,
+                Err(err) => fail(format, err),
+            };
+            // The path is the result itself, so unlike the messages of report it's printed even with --quiet.
+            match format {
+                OutputFormat::Text if *name_only => println!("{}", display_path(Path::new(&dir_name(&path)), *shell_escape)),
+                OutputFormat::Text => println!("{}", display_path(&path, *shell_escape)),
+                OutputFormat::Json if *name_only => {
+                    println!("{}", json!({ "action": "which", "alias": bookmark.alias, "path": path, "name": dir_name(&path) }));
+                }
+                OutputFormat::Json => println!("{}", json!({ "action": "which", "alias": bookmark.alias, "path": path })),
+            }
+        }
+        Commands::LastAdded => {
+            // Bookmarks from before created_at was recorded count as older than any that have it, and among those
+            // that tie, the one further down the list was added later.
+            let Some(bookmark) = config.bookmarks
+                .iter()
+                .enumerate()
+                .filter(|(_, bookmark)| !bookmark.system)
+                .max_by_key(|(index, bookmark)| (bookmark.created_at, *index))
+                .map(|(_, bookmark)| bookmark)
+            else {
+                fail(format, "You have no bookmarks.");
+            };
+            let path = match bookmark.target() {
+                Ok(path) => path,
+                Err(err) => fail(format, err),
+            };
+            // Like `which`, the path is the result itself, so it's printed even with --quiet.
+            match format {
+                OutputFormat::Text => println!("{}", path.display()),
+                OutputFormat::Json => println!("{}", json!({ "action": "last-added", "alias": bookmark.alias, "path": path })),
+            }
+        }
+        Commands::Has { alias } => {
+            let bookmark = config.bookmarks.iter().find(|b| b.alias == *alias);
+            match (format, bookmark) {
+                (OutputFormat::Json, _) => println!("{}", json!({ "action": "has", "alias": alias, "exists": bookmark.is_some() })),
+                (OutputFormat::Text, Some(bookmark)) if verbosity == Verbosity::Verbose => println!("{}", bookmark.path.display()),
+                (OutputFormat::Text, _) => {}
+            }
+            if bookmark.is_none() {
+                process::exit(1);
+            }
+        }
+        Commands::Open { alias } => {
+            let Some(bookmark) = config.bookmarks.iter().find(|b| b.alias == *alias) else {
+                fail(format, format!("No bookmark found with alias '{}'", alias));
+            };
+            let path = match bookmark.target() {
+                Ok(path) => path,
+                Err(err) => fail(format, err),
+            };
+            if !path.exists() {
+                fail(format, format!("'{}' no longer exists", path.display()));
+            }
+            if let Err(err) = bookmark.check_kind(&path) {
+                fail(format, err);
+            }
+
+            // The file manager keeps running on its own, so don't wait for it.
+            if let Err(err) = process::Command::new(FILE_MANAGER).arg(&path).spawn() {
+                fail(format, format!("Failed to launch '{}': {}", FILE_MANAGER, err));
+            }
+        }
+        Commands::Touch { alias } => {
+            let Some(bookmark) = config.bookmarks.iter_mut().find(|b| b.alias == *alias) else {
+                fail(format, format!("No bookmark found with alias '{}'", alias));
+            };
+            // Visits to system bookmarks couldn't be saved anywhere.
+            if let Err(err) = bookmark.check_changeable() {
+                fail(format, err);
+            }
+            bookmark.visits += 1;
+            bookmark.last_visited = Some(Utc::now());
+            let message = format!("Counted a visit to '{}', now at {} visit(s)", alias, bookmark.visits);
+            report(format, verbosity, &message, json!({ "action": "touch", "alias": alias, "visits": bookmark.visits }));
+            // Like a jump, this only updates statistics, so it leaves the undo snapshot alone.
+            write_config(&config, &config_path, save);
+        }
+        Commands::Copy { alias } => {
+            let Some(bookmark) = config.bookmarks.iter().find(|b| b.alias == *alias) else {
+                fail(format, format!("No bookmark found with alias '{}'", alias));
+            };
+            let path = match bookmark.target() {
+                Ok(path) => path,
+                Err(err) => fail(format, err),
+            };
+            match copy_to_clipboard(&path.to_string_lossy()) {
+                Ok(()) => {
+                    let message = format!("Copied '{}' to the clipboard", path.display());
+                    report(format, verbosity, &message, json!({ "action": "copy", "alias": bookmark.alias, "path": path }));
+                }
+                Err(err) if cli.strict => fail(format, err),
+                // The path is still useful without a clipboard, so print it for the user to copy by hand.
+                Err(err) => {
+                    eprintln!("Warning: {}. Printing the path instead.", err);
+                    println!("{}", path.display());
+                }
+            }
+        }
+        Commands::Pin { alias } | Commands::Unpin { alias } => {
+            let pin = matches!(cli.command, Commands::Pin { .. });
+            let Some(bookmark) = config.bookmarks.iter_mut().find(|b| b.alias == *alias) else {
+                fail(format, format!("No bookmark found with alias '{}'", alias));
+            };
+            if let Err(err) = bookmark.check_changeable() {
+                fail(format, err);
+            }
+            let state = if pin { "pinned" } else { "unpinned" };
+            if bookmark.pinned == pin {
+                let message = format!("'{}' is already {}", alias, state);
+                report(format, verbosity, &message, json!({ "action": if pin { "pin" } else { "unpin" }, "alias": alias, "changed": false }));
+                return;
+            }
+
+            bookmark.pinned = pin;
+            let message = format!("{} '{}'", if pin { "Pinned" } else { "Unpinned" }, alias);
+            report(format, verbosity, &message, json!({ "action": if pin { "pin" } else { "unpin" }, "alias": alias, "changed": true }));
+            save_config(&config, &config_path, save);
+        }
+        Commands::Tag { .. } | Commands::Untag { .. } => {
+            let (aliases, pattern, add, remove) = match &cli.command {
+                Commands::Tag { aliases, r#match, add, remove } => (aliases, r#match, add.as_slice(), remove.as_slice()),
+                Commands::Untag { aliases, r#match, tags } => (aliases, r#match, &[][..], tags.as_slice()),
+                _ => unreachable!("only tag and untag get here"),
+            };
+
+            let mut indices = BTreeSet::new();
+            let mut failed = false;
+            for alias in aliases {
+                match config.bookmarks.iter().position(|b| b.alias == *alias) {
+                    Some(index) if config.bookmarks[index].system => {
+                        report_error(format, config.bookmarks[index].check_changeable().unwrap_err());
+                        failed = true;
+                    }
+                    Some(index) => {
+                        indices.insert(index);
+                    }
+                    None => {
+                        report_error(format, format!("No bookmark found with alias '{}'", alias));
+                        failed = true;
+                    }
+                }
+            }
+            if let Some(pattern) = pattern {
+                let matched: Vec<usize> = (0..config.bookmarks.len())
+                    .filter(|&index| !config.bookmarks[index].system && glob_match(pattern, &config.bookmarks[index].alias))
+                    .collect();
+                if matched.is_empty() {
+                    report_error(format, format!("No bookmarks match '{}'", pattern));
+                    failed = true;
+                }
+                indices.extend(matched);
+            }
+
+            // Every bookmark is changed in memory first, so the config is saved once for all of them.
+            let mut changed = false;
+            for index in indices {
+                let bookmark = &mut config.bookmarks[index];
+                let added: Vec<&String> = add.iter().filter(|tag| !bookmark.tags.contains(tag)).collect();
+                let removed: Vec<&String> = remove.iter().filter(|tag| bookmark.tags.contains(tag)).collect();
+                bookmark.tags.extend(added.iter().map(|tag| tag.to_string()));
+                bookmark.tags.retain(|tag| !removed.contains(&tag));
+                changed |= !added.is_empty() || !removed.is_empty();
+
+                let mut changes = Vec::new();
+                if !added.is_empty() {
+                    changes.push(format!("added {}", added.iter().map(|tag| tag.as_str()).collect::<Vec<_>>().join(", ")));
+                }
+                if !removed.is_empty() {
+                    changes.push(format!("removed {}", removed.iter().map(|tag| tag.as_str()).collect::<Vec<_>>().join(", ")));
+                }
+                let message = if changes.is_empty() {
+                    format!("The tags of '{}' are unchanged", bookmark.alias)
+                } else {
+                    format!("Updated the tags of '{}': {}", bookmark.alias, changes.join("; "))
+                };
+                report(format, verbosity, &message, json!({ "action": "tag", "alias": bookmark.alias, "added": added, "removed": removed, "tags": bookmark.tags }));
+            }
+
+            if changed {
+                save_config(&config, &config_path, save);
+            }
+            if failed {
+                process::exit(1);
+            }
+        }
+        Commands::Tags => {
+            let tags: BTreeSet<&str> = config.bookmarks
+                .iter()
+                .flat_map(|bookmark| bookmark.tags.iter().map(String::as_str))
+                .collect();
+            for tag in tags {
+                println!("{}", tag);
+            }
+        }
+        Commands::Profiles => {
+            let mut profiles = vec!["default".to_string()];
+            let profiles_dir = match profiles_dir() {
+                Ok(profiles_dir) => profiles_dir,
+                Err(err) => fail(format, err),
+            };
+            if let Ok(entries) = fs::read_dir(profiles_dir) {
+                let mut names: Vec<String> = entries
+                    .filter_map(Result::ok)
+                    .map(|entry| entry.path())
+                    .filter(|path| path.extension().is_some_and(|extension| ["toml", "json", "yaml", "yml"].iter().any(|e| extension == *e)))
+                    .filter_map(|path| path.file_stem().map(|stem| stem.to_string_lossy().into_owned()))
+                    .collect();
+                names.sort();
+                profiles.extend(names);
+            }
+            let current = cli.profile.as_deref().unwrap_or("default");
+            for profile in profiles {
+                let marker = if profile == current { '*' } else { ' ' };
+                println!("{} {}", marker, profile);
+            }
+        }
+        Commands::Count { tag } => {
+            let count = config.bookmarks
+                .iter()
+                .filter(|bookmark| tag.as_ref().is_none_or(|tag| bookmark.tags.contains(tag)))
+                .count();
+            println!("{}", count);
+        }
+        Commands::Stats { json } => {
+            let broken = config.bookmarks.iter().filter(|bookmark| bookmark.is_missing()).count();
+            let total_jumps: u64 = config.bookmarks.iter().map(|bookmark| bookmark.visits).sum();
+            // Reversed so the first of several equally visited bookmarks wins.
+            let most_visited = config.bookmarks
+                .iter()
+                .rev()
+                .filter(|bookmark| bookmark.visits > 0)
+                .max_by_key(|bookmark| bookmark.visits);
+            // Bookmarks that were never jumped to sort before any visited one.
+            let least_recently_used = config.bookmarks.iter().min_by_key(|bookmark| bookmark.last_visited);
+
+            if *json || format == OutputFormat::Json {
+                let stats = json!({
+                    "bookmarks": config.bookmarks.len(),
+                    "broken": broken,
+                    "total_jumps": total_jumps,
+                    "most_visited": most_visited.map(|bookmark| &bookmark.alias),
+                    "least_recently_used": least_recently_used.map(|bookmark| &bookmark.alias),
+                });
+                println!("{}", serde_json::to_string_pretty(&stats).expect("Failed to serialize stats"));
+            } else {
+                let most_visited = most_visited
+                    .map(|bookmark| format!("{} ({} jumps)", bookmark.alias, bookmark.visits))
+                    .unwrap_or_else(|| "-".to_string());
+                let least_recently_used = least_recently_used
+                    .map(|bookmark| match bookmark.last_visited {
+                        Some(last_visited) => format!("{} ({})", bookmark.alias, last_visited.to_rfc3339_opts(SecondsFormat::Secs, true)),
+                        None => format!("{} (never)", bookmark.alias),
+                    })
+                    .unwrap_or_else(|| "-".to_string());
+                let rows = [
+                    ("Bookmarks", config.bookmarks.len().to_string()),
+                    ("Broken paths", broken.to_string()),
+                    ("Total jumps", total_jumps.to_string()),
+                    ("Most visited", most_visited),
+                    ("Least recently used", least_recently_used),
+                ];
+                let width = rows.iter().map(|(label, _)| label.len()).max().unwrap_or(0) + 1;
+                for (label, value) in rows {
+                    println!("{:<width$} {}", format!("{}:", label), value, width = width);
+                }
+            }
+        }
+        Commands::Export { file, jsonl } => {
+            let config = stored_config(&config);
+            let out: Box<dyn Write> = match file {
+                Some(file) => match File::create(file) {
+                    Ok(out) => Box::new(out),
+                    Err(err) => fail(format, format!("Failed to create '{}': {}", file.display(), err)),
+                },
+                None => Box::new(io::stdout().lock()),
+            };
+            let mut out = io::BufWriter::new(out);
+            let result = if *jsonl {
+                // Each bookmark is written as soon as it's serialized, so a reader can start on the first line
+                // before the last one is written.
+                config.bookmarks.iter().try_for_each(|bookmark| {
+                    serde_json::to_writer(&mut out, bookmark).map_err(io::Error::from)?;
+                    writeln!(out)
+                })
+            } else {
+                out.write_all(ConfigFormat::Toml.serialize(&config).as_bytes())
+            };
+            if let Err(err) = result.and_then(|()| out.flush()) {
+                let target = file.as_ref().map_or("stdout".to_string(), |file| format!("'{}'", file.display()));
+                fail(format, format!("Failed to write the export to {}: {}", target, err));
+            }
+            if let Some(file) = file {
+                say(verbosity, format!("Exported {} bookmark(s) to '{}'", config.bookmarks.len(), file.display()));
+            }
+        }
+        Commands::Import { file, from, merge, force, allow_hooks } => {
+            let merge = *merge || *from != ImportSource::Pomelo;
+            if *force && !merge {
+                fail(format, "--force only applies when merging. Pass --merge as well.");
+            }
+            let mut imported = match from {
+                ImportSource::Pomelo => {
+                    let file = file.as_ref().expect("clap requires a file without --from");
+                    match read_config(file) {
+                        Ok(imported) => imported.bookmarks,
+                        Err(err) => fail(format, err),
+                    }
+                }
+                source => {
+                    let file = match file {
+                        Some(file) => file.clone(),
+                        None => match default_database_path(*source) {
+                            Some(file) => file,
+                            None => fail(format, format!("Couldn't find the {:?} database. Pass its path instead.", source)),
+                        },
+                    };
+                    let entries = match read_database(*source, &file) {
+                        Ok(entries) => entries,
+                        Err(err) => fail(format, err),
+                    };
+                    let (bookmarks, missing) = bookmarks_from_database(entries);
+                    if missing > 0 {
+                        say(verbosity, format!("Skipped {} director(ies) that no longer exist.", missing));
+                    }
+                    bookmarks
+                }
+            };
+            // The `j` function evals on-jump commands, so a shared file mustn't be able to slip one in unnoticed.
+            if !allow_hooks {
+                let mut dropped = 0;
+                for bookmark in imported.iter_mut() {
+                    if let Some(hook) = bookmark.on_jump.take() {
+                        eprintln!("Warning: left out the on-jump command of '{}': {}", bookmark.alias, hook);
+                        dropped += 1;
+                    }
+                }
+                if dropped > 0 {
+                    eprintln!("Import again with --allow-hooks to keep them, if you trust these commands.");
+                }
+            }
+
+            if !merge {
+                say(verbosity, format!("Imported {} bookmark(s), replacing {} existing one(s).", imported.len(), config.bookmarks.len()));
+                config.bookmarks = imported;
+                backup_config(&config_path, save);
+                save_config(&config, &config_path, save);
+                return;
+            }
+
+            let (mut added, mut updated, mut skipped) = (0, 0, 0);
+            for bookmark in imported {
+                match config.bookmarks.iter_mut().find(|b| b.alias == bookmark.alias) {
+                    Some(existing) if *force => {
+                        *existing = bookmark;
+                        updated += 1;
+                    }
+                    Some(existing) => {
+                        say(verbosity, format!("Skipped '{}': a bookmark with this alias already exists", existing.alias));
+                        skipped += 1;
+                    }
+                    None => {
+                        config.bookmarks.push(bookmark);
+                        added += 1;
+                    }
+                }
+            }
+            backup_config(&config_path, save);
+            save_config(&config, &config_path, save);
+            say(verbosity, format!("Imported {} new, {} overwritten, {} skipped.", added, updated, skipped));
+        }
+        Commands::Sync { push } => {
+            let config_dir = match config_path.parent() {
+                Some(dir) if !dir.as_os_str().is_empty() => dir,
+                _ => Path::new("."),
+            };
+            if run_git(config_dir, &["rev-parse", "--is-inside-work-tree"]).is_err() {
+                fail(format, format!("'{}' is not in a git repository", config_dir.display()));
+            }
+            if !config_path.exists() {
+                fail(format, format!("'{}' doesn't exist yet, so there is nothing to sync", config_path.display()));
+            }
+
+            let file = config_path.file_name().expect("The config path names a file").to_string_lossy().into_owned();
+            let result = run_git(config_dir, &["add", "--", &file])
+                .and_then(|_| run_git(config_dir, &["status", "--porcelain", "--", &file]));
+            let changed = match result {
+                Ok(status) => !status.trim().is_empty(),
+                Err(err) => fail(format, err),
+            };
+            if changed {
+                let message = format!("Update pomelo bookmarks ({} bookmark(s))", config.bookmarks.len());
+                if let Err(err) = run_git(config_dir, &["commit", "-m", &message, "--", &file]) {
+                    fail(format, err);
+                }
+                say(verbosity, format!("Committed '{}'", config_path.display()));
+            } else {
+                say(verbosity, "No changes to commit.");
+            }
+            if *push {
+                if let Err(err) = run_git(config_dir, &["push"]) {
+                    fail(format, err);
+                }
+                say(verbosity, "Pushed to the upstream repository.");
+            }
+        }
+        Commands::Config { command: ConfigCommand::Get { key } } => {
+            let value = match get_setting(&config.settings, key) {
+                Ok(value) => value,
+                Err(err) => fail(format, err),
+            };
+            // Like `which`, the value is the result itself, so it's printed even with --quiet.
+            match (format, &value) {
+                (OutputFormat::Text, toml::Value::String(value)) => println!("{}", value),
+                (OutputFormat::Text, value) => println!("{}", value),
+                (OutputFormat::Json, value) => println!("{}", json!({ "action": "get", "key": key, "value": value })),
+            }
+        }
+        Commands::Config { command: ConfigCommand::Set { key, value } } => {
+            if let Err(err) = set_setting(&mut config.settings, key, value) {
+                fail(format, err);
+            }
+            let value = get_setting(&config.settings, key).expect("The setting was just set");
+            let message = format!("Set '{}' to {}", key, value);
+            report(format, verbosity, &message, json!({ "action": "set", "key": key, "value": value }));
+            save_config(&config, &config_path, save);
+        }
+        Commands::Convert { to } => {
+            if ConfigFormat::from_path(&config_path) == *to {
+                fail(format, format!("'{}' is already a {} file", config_path.display(), to.extension()));
+            }
+            let new_path = config_path.with_extension(to.extension());
+            if new_path.exists() {
+                fail(format, format!("'{}' already exists", new_path.display()));
+            }
+
+            write_config(&config, &new_path, save);
+            if config_path.exists() {
+                fs::remove_file(&config_path).expect("Failed to remove the old config file");
+            }
+            // The backup and the undo snapshot are configs too, so they're converted along with it, and so `restore`
+            // and `undo` still find them. The directory of `jump --back` moves over as it is.
+            for sibling_path in [get_backup_path, get_undo_path] {
+                let old_path = sibling_path(&config_path);
+                match read_config(&old_path) {
+                    Ok(snapshot) => {
+                        write_config(&snapshot, &sibling_path(&new_path), save);
+                        fs::remove_file(&old_path).expect("Failed to remove the old snapshot");
+                    }
+                    Err(ConfigError::Read { source, .. }) if source.kind() == io::ErrorKind::NotFound => {}
+                    Err(err) => eprintln!("Warning: left '{}' as it was, since it couldn't be converted. {}", old_path.display(), err),
+                }
+            }
+            let last_dir_path = get_last_dir_path(&config_path);
+            if last_dir_path.exists() {
+                fs::rename(&last_dir_path, get_last_dir_path(&new_path)).expect("Failed to move the last directory file");
+            }
+            let message = format!("Converted '{}' to '{}'", config_path.display(), new_path.display());
+            report(format, verbosity, &message, json!({ "action": "convert", "from": config_path, "to": new_path }));
+        }
+        Commands::Doctor => unreachable!("doctor runs before the config is loaded"),
+        Commands::Config { command: ConfigCommand::Path | ConfigCommand::Edit } => {
+            unreachable!("config path and config edit run before the config is loaded")
+        }
+        Commands::Restore => unreachable!("restore runs before the config is loaded"),
+        Commands::Undo => {
+            let undo_path = get_undo_path(&config_path);
+            let previous = match read_config(&undo_path) {
+                Ok(previous) => previous,
+                Err(ConfigError::Read { source, .. }) if source.kind() == io::ErrorKind::NotFound => {
+                    fail(format, "Nothing to undo.");
+                }
+                Err(err) => fail(format, err),
+            };
+
+            // Bookmarks from the system config aren't in either file, so they're left out of the comparison.
+            let mut changes = Vec::new();
+            for bookmark in config.bookmarks.iter().filter(|b| !b.system) {
+                match previous.bookmarks.iter().find(|b| b.alias == bookmark.alias) {
+                    None => changes.push(format!("Removed bookmark with alias '{}'", bookmark.alias)),
+                    Some(old) if old.path != bookmark.path => {
+                        changes.push(format!("Changed path of '{}' back to '{}'", old.alias, old.path.display()));
+                    }
+                    Some(_) => {}
+                }
+            }
+            for bookmark in &previous.bookmarks {
+                if !config.bookmarks.iter().any(|b| !b.system && b.alias == bookmark.alias) {
+                    changes.push(format!("Restored bookmark with alias '{}'", bookmark.alias));
+                }
+            }
+
+            fs::rename(&undo_path, &config_path).expect("Failed to restore undo snapshot");
+            if changes.is_empty() {
+                say(verbosity, "Reverted the last change.");
+            }
+            for change in changes {
+                say(verbosity, change);
+            }
+        }
+        Commands::Clear { yes } => {
+            // Like dedup and prune, this only deals with the user's own bookmarks, not those from the system config.
+            config.bookmarks.retain(|b| !b.system);
+            let count = config.bookmarks.len();
+            if count == 0 {
+                println!("You have no bookmarks.");
+                return;
+            }
+            if !yes {
+                if !io::stdin().is_terminal() {
+                    fail(format, "Refusing to clear bookmarks without confirmation. Pass --yes to skip it.");
+                }
+                eprint!("Remove all {} bookmarks? [y/N] ", count);
+                let mut answer = String::new();
+                io::stdin().read_line(&mut answer).expect("Failed to read confirmation");
+                if !matches!(answer.trim(), "y" | "Y" | "yes") {
+                    println!("Nothing was removed.");
+                    return;
+                }
+            }
+
+            config.bookmarks.clear();
+            backup_config(&config_path, save);
+            save_config(&config, &config_path, save);
+            say(verbosity, format!("Removed all {} bookmarks.", count));
+        }
+        Commands::Dedup { by } => {
+            config.bookmarks.retain(|b| !b.system);
+            let removed = dedup_bookmarks(&mut config.bookmarks, *by);
+            if removed.is_empty() {
+                println!("No duplicate bookmarks found.");
+                return;
+            }
+            for (bookmark, kept) in &removed {
+                say(verbosity, format!("Removed '{}' ({}), a duplicate of '{}'", bookmark.alias, bookmark.path.display(), kept));
+            }
+            say(verbosity, format!("Removed {} duplicate bookmark(s), {} remaining.", removed.len(), config.bookmarks.len()));
+            backup_config(&config_path, save);
+            save_config(&config, &config_path, save);
+        }
+        Commands::Prune { older_than } => {
+            config.bookmarks.retain(|b| !b.system);
+            let dry_run = cli.dry_run;
+            let cutoff = older_than.map(|age| Utc::now() - age);
+            // Bookmarks from before created_at was recorded, and never jumped to, have no known age and are kept.
+            let unused = |bookmark: &Bookmark| {
+                let last_used = bookmark.last_visited.or(bookmark.created_at);
+                matches!((cutoff, last_used), (Some(cutoff), Some(last_used)) if last_used < cutoff)
+            };
+            let (dead, alive): (Vec<Bookmark>, Vec<Bookmark>) = config.bookmarks
+                .into_iter()
+                .partition(|bookmark| bookmark.is_missing() || unused(bookmark));
+
+            if dead.is_empty() {
+                println!("No bookmarks to prune.");
+                return;
+            }
+
+            // A dry run is asked for its output, so only a real prune goes quiet.
+            let verb = if dry_run { "Would remove" } else { "Removed" };
+            let verbosity = if dry_run { Verbosity::Normal } else { verbosity };
+            for bookmark in &dead {
+                say(verbosity, format!("{} bookmark with alias '{}' ({})", verb, bookmark.alias, bookmark.path.display()));
+            }
+            say(verbosity, format!("{} {} bookmark(s), {} remaining.", verb, dead.len(), alive.len()));
+
+            if !dry_run {
+                config.bookmarks = alive;
+                backup_config(&config_path, save);
+                save_config(&config, &config_path, save);
+            }
+        }
+        Commands::Rebase { from, to, allow_missing } => {
+            let dry_run = cli.dry_run;
+            // Stored paths are canonical, so both sides are too where they exist. The old directory is usually gone
+            // by now, so it's made absolute with '.' and '..' resolved by hand instead.
+            let absolute = |path: &Path| expand_path(path).and_then(|path| match fs::canonicalize(&path) {
+                Ok(path) => Ok(path),
+                Err(_) => std::path::absolute(&path)
+                    .map(|path| normalize_path(&path))
+                    .map_err(|err| format!("Failed to resolve '{}': {}", path.display(), err)),
+            });
+            let (from, to) = match (absolute(from), absolute(to)) {
+                (Ok(from), Ok(to)) => (from, to),
+                (Err(err), _) | (_, Err(err)) => fail(format, err),
+            };
+            // A bookmark of `from` itself becomes `to` as it is, since joining an empty rest would add a trailing '/'.
+            let rebase = |path: &Path| {
+                let rest = path.strip_prefix(&from).ok()?;
+                Some(if rest.as_os_str().is_empty() { to.clone() } else { to.join(rest) })
+            };
+
+            // Templates are expanded on every jump, so their stored form can't be compared, and system bookmarks
+            // can't be changed.
+            let verb = if dry_run { "Would rewrite" } else { "Rewrote" };
+            let mut lines = Vec::new();
+            let mut rewritten = Vec::new();
+            let mut missing = Vec::new();
+            for bookmark in config.bookmarks.iter_mut().filter(|b| !b.system && !b.expand_on_jump) {
+                let mut changes = Vec::new();
+                for path in std::iter::once(&mut bookmark.path).chain(bookmark.alternate_paths.iter_mut()) {
+                    if let Some(new) = rebase(path) {
+                        changes.push(format!("'{}' -> '{}'", path.display(), new.display()));
+                        if !new.exists() {
+                            missing.push(format!("'{}'", new.display()));
+                        }
+                        *path = new;
+                    }
+                }
+                if !changes.is_empty() {
+                    lines.push(format!("{} '{}': {}", verb, bookmark.alias, changes.join(", ")));
+                    rewritten.push(bookmark.alias.clone());
+                }
+            }
+
+            if rewritten.is_empty() {
+                println!("No bookmarks are in '{}'.", from.display());
+                return;
+            }
+            if !missing.is_empty() && !allow_missing {
+                fail(format, format!("Nothing was changed, because these don't exist: {}. Use --allow-missing to rewrite the paths anyway.", missing.join(", ")));
+            }
+            lines.push(format!("{} {} bookmark(s).", verb, rewritten.len()));
+            // A dry run is asked for its output, so only a real rebase goes quiet.
+            let verbosity = if dry_run { Verbosity::Normal } else { verbosity };
+            report(format, verbosity, &lines.join("\n"), json!({ "action": "rebase", "from": from, "to": to, "aliases": rewritten, "dry_run": dry_run }));
+            if !dry_run {
+                backup_config(&config_path, save);
+                save_config(&config, &config_path, save);
+            }
+        }
+        Commands::Init { .. } | Commands::Completions { .. } => unreachable!("init and completions run before the config is found"),
+        Commands::ListAliases { complete } => {
+            match complete {
+                Some(prefix) => {
+                    let aliases: Vec<&str> = config.bookmarks.iter().map(|bookmark| bookmark.alias.as_str()).collect();
+                    for completion in complete_alias(&aliases, prefix) {
+                        println!("{}", completion);
+                    }
+                }
+                None => {
+                    for bookmark in &config.bookmarks {
+                        println!("{}", bookmark.alias);
+                    }
+                }
+            }
+        }
+    }
+    drop(lock);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAVE: SaveOptions =
+        SaveOptions { format: OutputFormat::Text, verbosity: Verbosity::Quiet, dry_run: false, no_save: false, preview_on_stderr: false };
+
+    // Returns an empty directory of its own for a test under the system's temp directory, named after the test.
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("pomelo-test-{}-{}", process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).expect("Failed to create the test directory");
+        dir
+    }
+
+    fn config_with(aliases: &[&str], dir: &Path) -> Config {
+        Config {
+            version: CONFIG_VERSION,
+            settings: Settings::default(),
+            bookmarks: aliases.iter().map(|alias| Bookmark::new(alias.to_string(), dir.to_path_buf())).collect(),
+            unknown: BTreeMap::new(),
+        }
+    }
+
+    fn aliases(config: &Config) -> Vec<&str> {
+        config.bookmarks.iter().map(|bookmark| bookmark.alias.as_str()).collect()
+    }
+
+    #[test]
+    fn interrupted_write_keeps_the_old_config() {
+        let dir = test_dir("interrupted-write");
+        let config_path = dir.join("config.toml");
+        write_config(&config_with(&["docs", "music"], &dir), &config_path, SAVE);
+
+        // A crash or full disk after the temporary file is created, but before it's renamed over the config,
+        // leaves it half-written next to the config.
+        let tmp_path = with_suffix(&config_path, ".tmp");
+        fs::write(&tmp_path, "[[bookmarks]]\nalias = \"do").unwrap();
+        let config = read_config(&config_path).expect("The old config should still read back");
+        assert_eq!(aliases(&config), ["docs", "music"]);
+
+        // The next save replaces the leftover instead of tripping over it.
+        write_config(&config_with(&["docs"], &dir), &config_path, SAVE);
+        assert!(!tmp_path.exists());
+        assert_eq!(aliases(&read_config(&config_path).unwrap()), ["docs"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn config_is_only_readable_by_its_owner() {
+        let dir = test_dir("permissions");
+        let config_path = dir.join("pomelo").join("config.toml");
+        write_config(&config_with(&["docs"], &dir), &config_path, SAVE);
+
+        let mode = |path: &Path| fs::metadata(path).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode(config_path.parent().unwrap()), 0o700);
+        assert_eq!(mode(&config_path), 0o600);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn unknown_fields_survive_a_save() {
+        let dir = test_dir("unknown-fields");
+        let config_path = dir.join("config.toml");
+        fs::write(
+            &config_path,
+            r#"version = 1
+sync = "nightly"
+
+[settings]
+finder = "sk"
+theme = { accent = "orange", dim = true }
+
+[[bookmarks]]
+alias = "docs"
+path = "/home/me/docs"
+color = "blue"
+shortcuts = ["d", "doc"]
+"#,
+        )
+        .unwrap();
+
+        let config = read_config(&config_path).unwrap();
+        let saved = ConfigFormat::Toml.parse(&ConfigFormat::Toml.serialize(&config)).unwrap();
+        assert_eq!(saved.unknown["sync"], Some(toml::Value::from("nightly")));
+        assert_eq!(saved.settings.finder, "sk");
+        let theme = saved.settings.unknown["theme"].as_ref().unwrap();
+        assert_eq!(theme["accent"].as_str(), Some("orange"));
+        assert_eq!(theme["dim"].as_bool(), Some(true));
+        let bookmark = &saved.bookmarks[0];
+        assert_eq!(bookmark.unknown["color"], Some(toml::Value::from("blue")));
+        assert_eq!(bookmark.unknown["shortcuts"], Some(toml::Value::from(vec!["d", "doc"])));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn unknown_nulls_survive_a_json_save() {
+        let contents = r#"{"version": 1, "mirror": null, "bookmarks": [{"alias": "docs", "path": "/home/me/docs", "color": null}]}"#;
+        let config = ConfigFormat::Json.parse(contents).unwrap();
+        let saved = ConfigFormat::Json.parse(&ConfigFormat::Json.serialize(&config)).unwrap();
+        assert_eq!(saved.unknown["mirror"], None);
+        assert_eq!(saved.bookmarks[0].unknown["color"], None);
+
+        // TOML has no null, so saving the same config as TOML leaves the keys out.
+        let saved = ConfigFormat::Toml.parse(&ConfigFormat::Toml.serialize(&config)).unwrap();
+        assert!(saved.unknown.is_empty());
+        assert!(saved.bookmarks[0].unknown.is_empty());
+    }
+
+    #[test]
+    fn config_path_must_not_be_a_directory() {
+        let dir = test_dir("config-path");
+        let err = get_config_path(Some(&dir), None).unwrap_err();
+        assert!(err.contains("is a directory") && err.contains("--config"), "{}", err);
+        assert_eq!(get_config_path(Some(&dir.join("config.toml")), None), Ok(dir.join("config.toml")));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn shell_quote_leaves_plain_words_alone() {
+        assert_eq!(shell_quote("/home/me/code/pomelo-2.0"), "/home/me/code/pomelo-2.0");
+        assert_eq!(shell_quote("user@host:/a,b=c+d%"), "user@host:/a,b=c+d%");
+        // An empty word would disappear, and a '~' would be expanded.
+        assert_eq!(shell_quote(""), "''");
+        assert_eq!(shell_quote("~/code"), "'~/code'");
+    }
+
+    #[test]
+    fn shell_quote_quotes_spaces_quotes_and_dollars() {
+        assert_eq!(shell_quote("/home/me/My Projects"), "'/home/me/My Projects'");
+        assert_eq!(shell_quote("$HOME/${USER}"), "'$HOME/${USER}'");
+        assert_eq!(shell_quote("it's \"here\""), "'it'\\''s \"here\"'");
+        assert_eq!(shell_quote("a`b`;c|d&e"), "'a`b`;c|d&e'");
+    }
+
+    #[test]
+    fn glob_match_stars_and_question_marks() {
+        assert!(glob_match("w*", "work"));
+        assert!(glob_match("*-api", "billing-api"));
+        assert!(glob_match("p?melo", "pomelo"));
+        assert!(glob_match("*o*o*", "pomelo"));
+        assert!(glob_match("*", ""));
+        assert!(!glob_match("w*", "awork"));
+        assert!(!glob_match("p?melo", "pmelo"));
+        assert!(!glob_match("docs", "docs2"));
+    }
+
+    #[test]
+    fn parse_duration_units() {
+        assert_eq!(parse_duration("90m"), Ok(chrono::Duration::minutes(90)));
+        assert_eq!(parse_duration("12h"), Ok(chrono::Duration::hours(12)));
+        assert_eq!(parse_duration("2w"), Ok(chrono::Duration::days(14)));
+        assert_eq!(parse_duration("6mo"), Ok(chrono::Duration::days(180)));
+        assert_eq!(parse_duration("1y"), Ok(chrono::Duration::days(365)));
+        assert!(parse_duration("d").is_err());
+        assert!(parse_duration("30").is_err());
+        assert!(parse_duration("3 days").is_err());
+        assert!(parse_duration("99999999999999y").is_err());
+    }
+
+    #[test]
+    fn expand_path_substitutes_home_and_variables() {
+        let home = dirs::home_dir().unwrap();
+        assert_eq!(expand_path(Path::new("~")), Ok(home.clone()));
+        assert_eq!(expand_path(Path::new("~/code")), Ok(home.join("code")));
+        // Only a '~' standing for the whole first component is the home directory.
+        assert_eq!(expand_path(Path::new("~me/code")), Ok(PathBuf::from("~me/code")));
+
+        env::set_var("POMELO_TEST_ROOT", "/srv/projects");
+        assert_eq!(expand_path(Path::new("$POMELO_TEST_ROOT/api")), Ok(PathBuf::from("/srv/projects/api")));
+        assert_eq!(expand_path(Path::new("${POMELO_TEST_ROOT}-old")), Ok(PathBuf::from("/srv/projects-old")));
+        assert_eq!(expand_path(Path::new("/tmp/$/cost")), Ok(PathBuf::from("/tmp/$/cost")));
+
+        let err = expand_path(Path::new("$POMELO_TEST_UNSET/api")).unwrap_err();
+        assert!(err.contains("'POMELO_TEST_UNSET'"), "{}", err);
+        assert!(expand_path(Path::new("${POMELO_TEST_ROOT")).is_err());
+    }
+
+    #[test]
+    fn jump_target_among_close_matches_is_ambiguous() {
+        let config = config_with(&["documents", "docker"], Path::new("/tmp"));
+        let candidates = find_jump_target(&config.bookmarks, "doc", false, false, &config.settings).unwrap_err();
+        let aliases: Vec<&str> = candidates.iter().map(|bookmark| bookmark.alias.as_str()).collect();
+        assert_eq!(aliases, ["docker", "documents"]);
+
+        // A clear lead in frecency decides, though.
+        let mut config = config;
+        config.bookmarks[0].visits = 3;
+        config.bookmarks[0].last_visited = Some(Utc::now());
+        assert_eq!(find_jump_target(&config.bookmarks, "doc", false, false, &config.settings).ok(), Some(0));
+    }
+
+    #[test]
+    fn jump_target_prefers_pinned_bookmarks() {
+        let mut config = config_with(&["documents", "docker"], Path::new("/tmp"));
+        config.bookmarks[0].pinned = true;
+        assert_eq!(find_jump_target(&config.bookmarks, "doc", false, false, &config.settings).ok(), Some(0));
+        // Pinning is worth as much as the highest frecency, so a bookmark visited far more often comes close again.
+        config.bookmarks[1].visits = 10;
+        config.bookmarks[1].last_visited = Some(Utc::now());
+        config.bookmarks[0].visits = 1;
+        config.bookmarks[0].last_visited = Some(Utc::now());
+        assert!(find_jump_target(&config.bookmarks, "doc", false, false, &config.settings).is_err());
     }
 }
@@ -0,0 +1,280 @@
+// Runs the pomelo binary against a config in a temp directory of each test's own, so the real config is never touched.
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::{self, Command, Output, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+struct Sandbox {
+    dir: PathBuf,
+}
+
+impl Sandbox {
+    // Creates an empty directory for the test, which is also HOME, with the config at 'config.toml' in it and the
+    // system config turned off.
+    fn new(name: &str) -> Self {
+        let dir = env::temp_dir().join(format!("pomelo-cli-{}-{}", process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).expect("Failed to create the test directory");
+        Sandbox { dir: dir.canonicalize().unwrap() }
+    }
+
+    fn config_path(&self) -> PathBuf {
+        self.dir.join("config.toml")
+    }
+
+    // Every save first snapshots the config here, so it only exists after one.
+    fn undo_path(&self) -> PathBuf {
+        self.dir.join("config.toml.prev")
+    }
+
+    // Creates a directory in the sandbox to bookmark and returns its path.
+    fn project(&self, name: &str) -> String {
+        let path = self.dir.join(name);
+        fs::create_dir_all(&path).unwrap();
+        path.to_str().unwrap().to_string()
+    }
+
+    fn config(&self) -> String {
+        fs::read_to_string(self.config_path()).unwrap()
+    }
+
+    fn command(&self, args: &[&str]) -> Command {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_pomelo"));
+        command
+            .args(args)
+            .current_dir(&self.dir)
+            .env("HOME", &self.dir)
+            .env("XDG_CONFIG_HOME", self.dir.join(".config"))
+            .env("POMELO_CONFIG", self.config_path())
+            .env("POMELO_SYSTEM_CONFIG", "");
+        command
+    }
+
+    fn run(&self, args: &[&str]) -> Output {
+        self.command(args).output().expect("Failed to run pomelo")
+    }
+}
+
+impl Drop for Sandbox {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+// Returns the (alias, path) of every bookmark in the sandbox's config, in the stored order.
+fn bookmarks(sandbox: &Sandbox) -> Vec<(String, String)> {
+    let config: toml::Value = toml::from_str(&sandbox.config()).unwrap();
+    config["bookmarks"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|bookmark| (bookmark["alias"].as_str().unwrap().to_string(), bookmark["path"].as_str().unwrap().to_string()))
+        .collect()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+#[test]
+fn remove_of_a_missing_alias_fails_without_saving() {
+    let sandbox = Sandbox::new("remove-missing");
+    let docs = sandbox.project("docs");
+    assert!(sandbox.run(&["add", "-a", "docs", "-p", &docs]).status.success());
+    let before = sandbox.config();
+    let _ = fs::remove_file(sandbox.undo_path());
+
+    let output = sandbox.run(&["remove", "-a", "music"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("music"), "{}", stderr(&output));
+    assert_eq!(sandbox.config(), before);
+    assert!(!sandbox.undo_path().exists(), "The config was saved again");
+}
+
+#[test]
+fn edit_of_a_missing_alias_fails_without_saving() {
+    let sandbox = Sandbox::new("edit-missing");
+    let docs = sandbox.project("docs");
+    assert!(sandbox.run(&["add", "-a", "docs", "-p", &docs]).status.success());
+    let before = sandbox.config();
+    let _ = fs::remove_file(sandbox.undo_path());
+
+    let output = sandbox.run(&["edit", "-a", "music", "-n", "songs"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("music"), "{}", stderr(&output));
+    assert_eq!(sandbox.config(), before);
+    assert!(!sandbox.undo_path().exists(), "The config was saved again");
+}
+
+#[test]
+fn add_rejects_a_duplicate_alias() {
+    let sandbox = Sandbox::new("add-duplicate");
+    let (docs, notes) = (sandbox.project("docs"), sandbox.project("notes"));
+    assert!(sandbox.run(&["add", "-a", "docs", "-p", &docs]).status.success());
+
+    let output = sandbox.run(&["add", "-a", "docs", "-p", &notes]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("docs"), "{}", stderr(&output));
+    assert_eq!(bookmarks(&sandbox), [("docs".to_string(), docs)]);
+}
+
+#[test]
+fn add_with_force_overwrites_a_duplicate_alias() {
+    let sandbox = Sandbox::new("add-force");
+    let (docs, notes, music) = (sandbox.project("docs"), sandbox.project("notes"), sandbox.project("music"));
+    assert!(sandbox.run(&["add", "-a", "docs", "-p", &docs]).status.success());
+    assert!(sandbox.run(&["add", "-a", "music", "-p", &music]).status.success());
+
+    for (flag, path) in [("--force", &notes), ("--overwrite", &docs)] {
+        let output = sandbox.run(&["add", "-a", "docs", "-p", path, flag]);
+        assert!(output.status.success(), "{}", stderr(&output));
+        // The bookmark is updated in place rather than added again.
+        assert_eq!(bookmarks(&sandbox), [("docs".to_string(), path.clone()), ("music".to_string(), music.clone())]);
+    }
+}
+
+#[test]
+fn config_flag_takes_precedence_over_pomelo_config() {
+    let sandbox = Sandbox::new("config-flag");
+    let docs = sandbox.project("docs");
+    let work_config = sandbox.dir.join("work.toml");
+    let work_config = work_config.to_str().unwrap();
+
+    let output = sandbox.run(&["--config", work_config, "add", "-a", "docs", "-p", &docs]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(!sandbox.config_path().exists(), "POMELO_CONFIG's file was written too");
+    assert!(fs::read_to_string(work_config).unwrap().contains(&docs));
+
+    let output = sandbox.run(&["--config", work_config, "which", "-a", "docs"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), docs);
+    assert!(!sandbox.run(&["which", "-a", "docs"]).status.success());
+}
+
+#[test]
+fn config_path_that_is_a_directory_is_an_error() {
+    let sandbox = Sandbox::new("config-dir");
+    let docs = sandbox.project("docs");
+    for args in [&["list"][..], &["add", "-a", "docs", "-p", &docs]] {
+        let output = sandbox.command(args).env("POMELO_CONFIG", &sandbox.dir).output().unwrap();
+        assert_eq!(output.status.code(), Some(1));
+        let message = stderr(&output);
+        assert!(message.contains("is a directory, but it has to be a file"), "{}", message);
+        assert!(message.contains("POMELO_CONFIG"), "{}", message);
+        assert!(!message.contains("panicked"), "{}", message);
+    }
+}
+
+#[test]
+fn init_and_completions_work_with_a_broken_config() {
+    let sandbox = Sandbox::new("init-broken-config");
+    fs::write(sandbox.config_path(), "[[bookmarks]\n").unwrap();
+    for args in [["init", "bash"], ["completions", "bash"]] {
+        let output = sandbox.run(&args);
+        assert!(output.status.success(), "{}", stderr(&output));
+        assert!(String::from_utf8_lossy(&output.stdout).contains("pomelo"));
+    }
+}
+
+#[test]
+fn reading_commands_dont_wait_for_the_lock() {
+    let sandbox = Sandbox::new("lock-readers");
+    let docs = sandbox.project("docs");
+    assert!(sandbox.run(&["add", "-a", "docs", "-p", &docs]).status.success());
+
+    // Another pomelo process in the middle of a change holds the lock.
+    let lock = fs::File::create(sandbox.dir.join("config.toml.lock")).unwrap();
+    lock.lock().unwrap();
+    for args in [&["init", "bash"][..], &["completions", "zsh"], &["list"], &["list-aliases"], &["config", "path"], &["which", "-a", "docs"]] {
+        let mut child = sandbox.command(args).stdout(Stdio::null()).stderr(Stdio::null()).spawn().unwrap();
+        let started = Instant::now();
+        while child.try_wait().unwrap().is_none() {
+            if started.elapsed() > Duration::from_secs(10) {
+                child.kill().unwrap();
+                panic!("`pomelo {}` waited for the lock", args.join(" "));
+            }
+            thread::sleep(Duration::from_millis(20));
+        }
+    }
+}
+
+#[test]
+fn edit_refuses_to_take_an_alias_in_use() {
+    let sandbox = Sandbox::new("edit-taken-alias");
+    let (docs, notes) = (sandbox.project("docs"), sandbox.project("notes"));
+    assert!(sandbox.run(&["add", "-a", "docs", "-p", &docs]).status.success());
+    assert!(sandbox.run(&["add", "-a", "notes", "-p", &notes]).status.success());
+    let before = sandbox.config();
+
+    let output = sandbox.run(&["edit", "-a", "docs", "-n", "notes"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("already exists"), "{}", stderr(&output));
+    assert_eq!(sandbox.config(), before);
+}
+
+#[test]
+fn dry_run_jump_prints_only_the_path() {
+    let sandbox = Sandbox::new("dry-run-jump");
+    let docs = sandbox.project("docs");
+
+    let output = sandbox.run(&["--dry-run", "jump", "-a", "docs", "--create"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), format!("{}\n", docs));
+    assert!(stderr(&output).contains("[[bookmarks]]"), "{}", stderr(&output));
+    assert!(!sandbox.config_path().exists());
+}
+
+#[test]
+fn add_names_a_file_bookmark_without_its_extension() {
+    let sandbox = Sandbox::new("add-file-alias");
+    fs::write(sandbox.dir.join("notes.md"), "").unwrap();
+    let output = sandbox.run(&["add", "-p", "notes.md"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(bookmarks(&sandbox), [("notes".to_string(), sandbox.dir.join("notes.md").to_str().unwrap().to_string())]);
+}
+
+#[test]
+fn strict_turns_doctor_and_system_config_warnings_into_errors() {
+    let sandbox = Sandbox::new("strict-warnings");
+    let gone = sandbox.dir.join("gone");
+    assert!(sandbox.run(&["add", "-a", "gone", "-p", gone.to_str().unwrap(), "--allow-missing"]).status.success());
+    assert!(sandbox.run(&["doctor"]).status.success());
+    assert_eq!(sandbox.run(&["--strict", "doctor"]).status.code(), Some(1));
+
+    let system_config = sandbox.dir.join("system.toml");
+    fs::write(&system_config, "[[bookmarks]\n").unwrap();
+    let output = sandbox.command(&["list-aliases"]).env("POMELO_SYSTEM_CONFIG", &system_config).output().unwrap();
+    assert!(output.status.success());
+    assert!(stderr(&output).contains("Warning: ignoring the system config"), "{}", stderr(&output));
+    let output = sandbox.command(&["--strict", "list-aliases"]).env("POMELO_SYSTEM_CONFIG", &system_config).output().unwrap();
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn undo_works_after_convert() {
+    let sandbox = Sandbox::new("convert-undo");
+    let (docs, notes) = (sandbox.project("docs"), sandbox.project("notes"));
+    assert!(sandbox.run(&["add", "-a", "docs", "-p", &docs]).status.success());
+    assert!(sandbox.run(&["add", "-a", "notes", "-p", &notes]).status.success());
+    assert!(sandbox.run(&["remove", "-a", "docs"]).status.success());
+
+    let output = sandbox.run(&["convert", "--to", "json"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let json_config = sandbox.dir.join("config.json");
+    for suffix in ["", ".bak", ".prev"] {
+        assert!(!sandbox.dir.join(format!("config.toml{}", suffix)).exists(), "config.toml{} was left behind", suffix);
+    }
+    let run = |args: &[&str]| sandbox.command(args).env("POMELO_CONFIG", &json_config).output().unwrap();
+    let aliases = || String::from_utf8(run(&["list-aliases"]).stdout).unwrap();
+
+    // The backup from before `remove` is now JSON as well.
+    let backup = fs::read_to_string(sandbox.dir.join("config.json.bak")).unwrap();
+    assert!(backup.contains("\"alias\": \"docs\""), "{}", backup);
+
+    let output = run(&["undo"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(aliases(), "docs\nnotes\n");
+}